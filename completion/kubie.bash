@@ -1,47 +1,34 @@
-#Kubie completion script
 
-_kubiecomplete()
-{
-    local cur prev
-
-    cur=${COMP_WORDS[COMP_CWORD]}
-    prev=${COMP_WORDS[COMP_CWORD-1]}
-
-    { \unalias command; \unset -f command; } >/dev/null 2>&1 || true
-
-    case ${COMP_CWORD} in
-        1)
-            cmds="ctx edit edit-config exec help info lint ns"
-            COMPREPLY=($(command printf "%s\n" $cmds | command grep -e "^$cur" | command xargs))
-            ;;
-        2)
-            case ${prev} in
-                ctx)
-                    COMPREPLY=($(command kubie ctx | command grep -e "^$cur" | command xargs))
-                    ;;
-                edit)
-                    COMPREPLY=($(command kubie ctx | command grep -e "^$cur" | command xargs))
-                    ;;
-                exec)
-                    COMPREPLY=($(command kubie ctx | command grep -e "^$cur" | command xargs))
-                    ;;
-                ns)
-                    COMPREPLY=($(command kubie ns | command grep -e "^$cur" | command xargs))
-                    ;;
-            esac
-            ;;
-        3)
-            prevprev=${COMP_WORDS[COMP_CWORD-2]}
-            case ${prevprev} in
-                exec)
-                    COMPREPLY=($(command kubie exec ${prev} default kubectl get namespaces|command tail -n+2|command awk '{print $1}'| command grep -e "^$cur" |command xargs))
-                    ;;
-            esac
-            ;;
-        *)
-            COMPREPLY=()
-            ;;
-    esac
+_clap_complete_kubie() {
+    local IFS=$'\013'
+    local _CLAP_COMPLETE_INDEX=${COMP_CWORD}
+    local _CLAP_COMPLETE_COMP_TYPE=${COMP_TYPE}
+    if compopt +o nospace 2> /dev/null; then
+        local _CLAP_COMPLETE_SPACE=false
+    else
+        local _CLAP_COMPLETE_SPACE=true
+    fi
+    local words=("${COMP_WORDS[@]}")
+    if [[ "${BASH_VERSINFO[0]}" -ge 4 ]]; then
+        words[COMP_CWORD]="$2"
+    fi
+    COMPREPLY=( $( \
+        _CLAP_IFS="$IFS" \
+        _CLAP_COMPLETE_INDEX="$_CLAP_COMPLETE_INDEX" \
+        _CLAP_COMPLETE_COMP_TYPE="$_CLAP_COMPLETE_COMP_TYPE" \
+        _CLAP_COMPLETE_SPACE="$_CLAP_COMPLETE_SPACE" \
+        COMPLETE="bash" \
+        "kubie" -- "${words[@]}" \
+    ) )
+    if [[ $? != 0 ]]; then
+        unset COMPREPLY
+    elif [[ $_CLAP_COMPLETE_SPACE == false ]] && [[ "${COMPREPLY-}" =~ [=/:]$ ]]; then
+        compopt -o nospace
+    fi
 }
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -o nospace -o bashdefault -o nosort -F _clap_complete_kubie kubie
+else
+    complete -o nospace -o bashdefault -F _clap_complete_kubie kubie
+fi
 
-complete -F _kubiecomplete kubie
@@ -0,0 +1,84 @@
+use std::collections::BTreeSet;
+
+use clap_complete::engine::CompletionCandidate;
+
+use crate::kubeconfig;
+use crate::settings::Settings;
+use crate::state::State;
+use crate::vars;
+
+/// Names of the installed contexts, described by the settings. Completers cannot
+/// report errors, nothing is completed when the settings or kubeconfigs cannot be loaded.
+pub fn contexts() -> Vec<CompletionCandidate> {
+    let Ok(settings) = Settings::load() else {
+        return vec![];
+    };
+    context_candidates(&settings)
+}
+
+/// Names of the installed contexts and of the aliases defined in the settings, for `kubie ctx`.
+pub fn contexts_and_aliases() -> Vec<CompletionCandidate> {
+    let Ok(settings) = Settings::load() else {
+        return vec![];
+    };
+    let mut candidates = context_candidates(&settings);
+    for context_settings in &settings.contexts {
+        if let Some(alias) = &context_settings.alias {
+            if settings.resolve_context_alias(alias) != alias {
+                candidates.push(
+                    CompletionCandidate::new(alias).help(Some(format!("alias of {}", context_settings.pattern).into())),
+                );
+            }
+        }
+    }
+    candidates
+}
+
+fn context_candidates(settings: &Settings) -> Vec<CompletionCandidate> {
+    let Ok(installed) = kubeconfig::get_installed_contexts(settings) else {
+        return vec![];
+    };
+
+    installed
+        .contexts
+        .iter()
+        .map(|c| {
+            let metadata = settings.context_metadata(&c.item.name);
+            CompletionCandidate::new(&c.item.name).help(metadata.description.map(Into::into))
+        })
+        .collect()
+}
+
+/// Namespaces cached the last time they were listed with kubectl. Inside of a kubie shell, only the
+/// namespaces of the current context are completed.
+pub fn namespaces() -> Vec<CompletionCandidate> {
+    let Ok(state) = State::load() else {
+        return vec![];
+    };
+
+    let current_context = vars::is_kubie_active()
+        .then(|| kubeconfig::get_current_config().ok())
+        .flatten()
+        .and_then(|config| config.current_context);
+
+    let namespaces: BTreeSet<_> = match current_context.and_then(|ctx| state.namespace_cache.get(&ctx)) {
+        Some(namespaces) => namespaces.iter().collect(),
+        None => state.namespace_cache.values().flatten().collect(),
+    };
+
+    namespaces.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Contexts and namespaces pinned with `kubie pin`.
+pub fn pinned() -> Vec<CompletionCandidate> {
+    let Ok(state) = State::load() else {
+        return vec![];
+    };
+
+    let contexts = state.pinned_contexts.iter().cloned();
+    let namespaces = state
+        .pinned_namespaces
+        .iter()
+        .flat_map(|(context, namespaces)| namespaces.iter().map(move |ns| format!("{context}/{ns}")));
+    contexts.chain(namespaces).map(CompletionCandidate::new).collect()
+}
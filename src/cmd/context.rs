@@ -1,15 +1,21 @@
-use anyhow::{Context, Result};
+use std::env;
+use std::io::{self, IsTerminal};
+
+use anyhow::{bail, Context, Result};
 use skim::SkimOptions;
 
-use crate::cmd::{select_or_list_context, SelectResult};
-use crate::kubeconfig::{self, Installed};
+use crate::cmd::{history, select_or_list_context, SelectResult};
+use crate::ioutil;
+use crate::kubeconfig::{self, Installed, KubeConfig};
 use crate::kubectl;
+use crate::selector::Selector;
 use crate::session::Session;
 use crate::settings::Settings;
-use crate::shell::spawn_shell;
-use crate::state::State;
+use crate::shell::{self, spawn_shell};
+use crate::state::{paths, State};
 use crate::vars;
 
+/// Enter the context with the given namespace, or with the namespace of the kubeconfig if `None`.
 fn enter_context(
     settings: &Settings,
     installed: Installed,
@@ -17,11 +23,12 @@ fn enter_context(
     namespace_name: Option<&str>,
     recursive: bool,
 ) -> Result<()> {
-    let state = State::load()?;
-    let mut session = Session::load()?;
-
-    let namespace_name =
-        namespace_name.or_else(|| state.namespace_history.get(context_name).and_then(|s| s.as_deref()));
+    let global = settings.behavior.is_global() && !recursive;
+    let mut session = if global {
+        Session::load_from(paths::global_session())?
+    } else {
+        Session::load()?
+    };
 
     let kubeconfig = if context_name == "-" {
         let previous_ctx = session
@@ -47,23 +54,112 @@ fn enter_context(
     }
 
     if vars::is_kubie_active() && !recursive {
+        let old_kubeconfig = kubeconfig::get_current_config()?;
         let path = kubeconfig::get_kubeconfig_path()?;
         kubeconfig.write_to_file(path.as_path())?;
         session.save(None)?;
+        record_history(&kubeconfig)?;
+        shell::sync_env_file(settings, &session, &old_kubeconfig, &kubeconfig)?;
+        shell::hooks::run_switch_hooks(settings, &old_kubeconfig, &kubeconfig);
+    } else if global {
+        enter_global(settings, &kubeconfig, &session)?;
+        record_history(&kubeconfig)?;
     } else {
+        // The shell only returns once it exits, record the context it is entered with.
+        record_history(&kubeconfig)?;
         spawn_shell(settings, kubeconfig, &session)?;
     }
 
     Ok(())
 }
 
+/// Record the context and namespace which were switched to in the history.
+fn record_history(kubeconfig: &KubeConfig) -> Result<()> {
+    State::modify(|state| {
+        state.record_history(
+            &kubeconfig.contexts[0].name,
+            kubeconfig.contexts[0].context.namespace.as_deref(),
+        );
+        Ok(())
+    })
+}
+
+/// Write the kubeconfig and session of the global mode, see `behavior.mode`. The switch hooks run
+/// as they do when `kubie ns` switches the global kubeconfig.
+fn enter_global(settings: &Settings, kubeconfig: &KubeConfig, session: &Session) -> Result<()> {
+    let path = paths::global_kubeconfig();
+    let old_kubeconfig: Option<KubeConfig> = ioutil::read_yaml(path).ok();
+    kubeconfig.write_to_file(path)?;
+    session.save(Some(paths::global_session()))?;
+    if let Some(old_kubeconfig) = old_kubeconfig {
+        shell::hooks::run_switch_hooks(settings, &old_kubeconfig, kubeconfig);
+    }
+
+    if env::var_os("KUBECONFIG").is_none_or(|value| !env::split_paths(&value).any(|p| p == path)) {
+        eprintln!(
+            "Warning: KUBECONFIG does not point to {}, set it in your shell's configuration file to use the global mode.",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Filters of the contexts listed in the picker.
+#[derive(Debug, Default)]
+pub struct ContextFilter {
+    /// Selectors which the tags of the contexts must match.
+    pub tags: Vec<String>,
+    /// Only list the pinned contexts.
+    pub pinned: bool,
+}
+
+/// Resolve the name given to `kubie ctx` to a context, according to `behavior.context_matching`
+/// when no context has this exact name. When several contexts match, the picker is opened over them
+/// with the name as its query. Returns `None` when the picker is cancelled or the contexts listed.
+fn resolve_context_name(
+    settings: &Settings,
+    skim_options: &mut SkimOptions,
+    installed: &mut Installed,
+    context_name: &str,
+) -> Result<Option<String>> {
+    let context_name = settings.resolve_context_alias(context_name);
+    if context_name == "-" || installed.find_context_by_name(context_name).is_some() {
+        return Ok(Some(context_name.to_string()));
+    }
+
+    let matching = settings.behavior.context_matching;
+    let matches: Vec<_> = installed
+        .contexts
+        .iter()
+        .filter(|c| matching.matches(&c.item.name, context_name))
+        .map(|c| c.item.name.clone())
+        .collect();
+    match matches.len() {
+        // Let the context lookup report the missing context.
+        0 => Ok(Some(context_name.to_string())),
+        1 => Ok(matches.into_iter().next()),
+        _ if !io::stdout().is_terminal() => {
+            bail!("{} matches multiple contexts: {}", context_name, matches.join(", "));
+        }
+        _ => {
+            installed.contexts.retain(|c| matches.contains(&c.item.name));
+            skim_options.query = Some(context_name.to_string());
+            match select_or_list_context(settings, skim_options, installed)? {
+                SelectResult::Selected(x) => Ok(Some(x)),
+                _ => Ok(None),
+            }
+        }
+    }
+}
+
 pub fn context(
     settings: &Settings,
-    skim_options: &SkimOptions,
+    skim_options: &mut SkimOptions,
     context_name: Option<String>,
     namespace_name: Option<String>,
     kubeconfigs: Vec<String>,
     recursive: bool,
+    filter: ContextFilter,
 ) -> Result<()> {
     let mut installed = if kubeconfigs.is_empty() {
         kubeconfig::get_installed_contexts(settings)?
@@ -71,13 +167,56 @@ pub fn context(
         kubeconfig::get_kubeconfigs_contexts(&kubeconfigs)?
     };
 
+    let state = State::load()?;
+    if filter.pinned {
+        installed
+            .contexts
+            .retain(|c| state.pinned_contexts.contains(&c.item.name));
+    }
+
+    let selector = Selector::parse_all(&filter.tags)?;
+    if !selector.is_empty() {
+        installed
+            .contexts
+            .retain(|c| selector.matches(&settings.context_metadata(&c.item.name).tags));
+    }
+
+    // `@N` enters the Nth most recently used context and namespace, unless a context has this name.
+    // The namespace of the record is used as is, even when it has none.
+    let history_ref = context_name
+        .as_deref()
+        .filter(|name| installed.find_context_by_name(name).is_none())
+        .and_then(history::parse_ref);
+    if let Some(index) = history_ref {
+        let record = history::get(&state, index)?;
+        let namespace_name = namespace_name.or(record.namespace);
+        return enter_context(
+            settings,
+            installed,
+            &record.context,
+            namespace_name.as_deref(),
+            recursive,
+        );
+    }
+
     let context_name = match context_name {
-        Some(context_name) => context_name,
-        None => match select_or_list_context(skim_options, &mut installed)? {
+        Some(context_name) => match resolve_context_name(settings, skim_options, &mut installed, &context_name)? {
+            Some(context_name) => context_name,
+            None => return Ok(()),
+        },
+        None => match select_or_list_context(settings, skim_options, &mut installed)? {
             SelectResult::Selected(x) => x,
             _ => return Ok(()),
         },
     };
 
+    let namespace_name = namespace_name.or_else(|| state.namespace_history.get(&context_name).cloned().flatten());
     enter_context(settings, installed, &context_name, namespace_name.as_deref(), recursive)
 }
+
+/// Enter the context and namespace of a record of the history. The namespace remembered for the
+/// context is not used when the record has no namespace.
+pub fn enter_record(settings: &Settings, context_name: &str, namespace_name: Option<&str>) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    enter_context(settings, installed, context_name, namespace_name, false)
+}
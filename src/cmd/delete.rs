@@ -10,7 +10,7 @@ pub fn delete_context(settings: &Settings, skim_options: &SkimOptions, context_n
 
     let context_name = match context_name {
         Some(context_name) => context_name,
-        None => match select_or_list_context(skim_options, &mut installed)? {
+        None => match select_or_list_context(settings, skim_options, &mut installed)? {
             SelectResult::Selected(x) => x,
             _ => return Ok(()),
         },
@@ -0,0 +1,61 @@
+use std::env;
+
+use anyhow::Result;
+
+use crate::cmd::meta::DoctorKind;
+use crate::cmd::print_table;
+use crate::settings::{Settings, ShellSetting};
+use crate::shell;
+
+pub fn doctor(settings: &Settings, kind: DoctorKind) -> Result<()> {
+    match kind {
+        DoctorKind::Shell => doctor_shell(settings),
+    }
+}
+
+/// Shorten long command lines, such as shells started with an inline script.
+fn truncate(command: &str, max_chars: usize) -> String {
+    match command.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &command[..end]),
+        None => command.to_string(),
+    }
+}
+
+fn doctor_shell(settings: &Settings) -> Result<()> {
+    let ancestry = shell::ancestry()?;
+    print_table(
+        &["PID", "SHELL", "COMMAND"],
+        ancestry
+            .iter()
+            .map(|a| {
+                vec![
+                    a.pid.to_string(),
+                    a.kind.map(|k| k.name()).unwrap_or("-").to_string(),
+                    truncate(&a.command, 100),
+                ]
+            })
+            .collect(),
+    );
+    println!();
+
+    let env_shell = env::var("SHELL").unwrap_or_default();
+    println!("SHELL environment variable: {env_shell}");
+
+    let detected = ancestry.iter().find_map(|a| a.kind);
+    match (&settings.shell, detected) {
+        (Some(ShellSetting::Name(name)), _) => println!("Kubie will spawn: {name} (from the shell setting)"),
+        (Some(ShellSetting::Custom(custom)), _) => {
+            println!("Kubie will spawn: {} (custom shell setting)", custom.command)
+        }
+        (None, Some(kind)) => println!("Kubie will spawn: {} (detected from the process tree)", kind.name()),
+        (None, None) => match shell::detect_from_env() {
+            Some(kind) => println!(
+                "Kubie will spawn: {} (from the SHELL environment variable)",
+                kind.name()
+            ),
+            None => println!("Kubie will spawn: nothing, no supported shell was found"),
+        },
+    }
+
+    Ok(())
+}
@@ -37,7 +37,7 @@ pub fn edit_context(settings: &Settings, skim_options: &SkimOptions, context_nam
 
     let context_name = match context_name {
         Some(context_name) => context_name,
-        None => match select_or_list_context(skim_options, &mut installed)? {
+        None => match select_or_list_context(settings, skim_options, &mut installed)? {
             SelectResult::Selected(x) => x,
             _ => return Ok(()),
         },
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::exports;
+use crate::kubeconfig;
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::shell::{self, ShellKind};
+use crate::state::State;
+use crate::vars;
+
+/// Variables set by `kubie env`, which are unset by `kubie env --unset`. The variables of the
+/// context settings are unset as well.
+const EVAL_VARS: &[&str] = &[
+    "KUBECONFIG",
+    "KUBIE_ACTIVE",
+    "KUBIE_CONTEXT",
+    "KUBIE_DEPTH",
+    "KUBIE_EVAL",
+    "KUBIE_EVAL_OLD_KUBECONFIG",
+    "KUBIE_KUBECONFIG",
+    "KUBIE_NAMESPACE",
+    "KUBIE_SESSION",
+];
+
+/// Whether the current environment was created by `kubie env`, rather than by a kubie shell.
+fn is_eval_active() -> bool {
+    env::var("KUBIE_EVAL").is_ok_and(|v| v == "1")
+}
+
+/// Variables of the context settings currently exported, using the context and namespace names
+/// exported by `kubie env`.
+fn current_context_env(settings: &Settings) -> BTreeMap<String, String> {
+    match (env::var("KUBIE_CONTEXT"), env::var("KUBIE_NAMESPACE")) {
+        (Ok(context_name), Ok(namespace_name)) => settings.context_env(&context_name, &namespace_name),
+        _ => BTreeMap::new(),
+    }
+}
+
+/// Remove the kubeconfig and session files written by the previous `kubie env` call.
+fn remove_eval_files() -> Result<()> {
+    if let Some(path) = env::var_os("KUBIE_KUBECONFIG") {
+        exports::remove(Path::new(&path))?;
+    }
+    if let Some(path) = env::var_os("KUBIE_SESSION") {
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(anyhow!("Could not remove {}: {}", path.to_string_lossy(), err));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn env(
+    settings: &Settings,
+    context_name: Option<String>,
+    namespace_name: Option<String>,
+    shell: Option<ShellKind>,
+    ttl: Option<Duration>,
+    unset: bool,
+) -> Result<()> {
+    if vars::is_kubie_active() && !is_eval_active() {
+        bail!("Already in a kubie shell, use `kubie ctx` to switch the context.");
+    }
+    let kind = match shell {
+        Some(kind) => kind,
+        None => shell::detect()?,
+    };
+
+    // Variables of the previous `kubie env` call, unset unless they are set again.
+    let mut env_vars: BTreeMap<String, Option<String>> = BTreeMap::new();
+    if is_eval_active() {
+        for name in current_context_env(settings).into_keys() {
+            env_vars.insert(name, None);
+        }
+        for name in EVAL_VARS {
+            env_vars.insert(name.to_string(), None);
+        }
+    }
+
+    // Value of KUBECONFIG before the first `kubie env` call, restored by `kubie env --unset`.
+    let old_kubeconfig = if is_eval_active() {
+        env::var("KUBIE_EVAL_OLD_KUBECONFIG").ok()
+    } else {
+        env::var("KUBECONFIG").ok()
+    };
+
+    if unset {
+        if !is_eval_active() {
+            bail!("Not in an environment created by `kubie env`.");
+        }
+        remove_eval_files()?;
+        env_vars.insert("KUBECONFIG".into(), old_kubeconfig);
+        print!("{}", shell::render_env(kind, &env_vars)?);
+        return Ok(());
+    }
+
+    let (Some(context_name), Some(namespace_name)) = (context_name, namespace_name) else {
+        bail!("A context and a namespace are required.");
+    };
+
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let kubeconfig = installed.make_kubeconfig_for_context(&context_name, Some(&namespace_name))?;
+
+    let mut session = Session::load()?;
+    session.add_history_entry(&context_name, Some(&namespace_name));
+
+    // The files of the previous call are only removed once the new ones are written, the shell keeps
+    // using them if anything fails.
+    let config_path = exports::create(&kubeconfig, ttl.unwrap_or(settings.behavior.export_ttl))?;
+    let session_path = exports::session_path(&config_path);
+    if let Err(err) = session.save(Some(&session_path)) {
+        let _ = exports::remove(&config_path);
+        return Err(err);
+    }
+
+    State::modify(|state| {
+        state.record_history(&context_name, Some(&namespace_name));
+        Ok(())
+    })?;
+
+    if let Err(err) = remove_eval_files() {
+        eprintln!("Warning: could not remove the files of the previous environment: {err}");
+    }
+
+    let config_path = config_path.to_string_lossy().into_owned();
+    for (name, value) in settings.context_env(&context_name, &namespace_name) {
+        env_vars.insert(name, Some(value));
+    }
+    env_vars.insert("KUBECONFIG".into(), Some(config_path.clone()));
+    env_vars.insert("KUBIE_ACTIVE".into(), Some("1".into()));
+    env_vars.insert("KUBIE_CONTEXT".into(), Some(context_name));
+    env_vars.insert("KUBIE_DEPTH".into(), Some("1".into()));
+    env_vars.insert("KUBIE_EVAL".into(), Some("1".into()));
+    env_vars.insert("KUBIE_EVAL_OLD_KUBECONFIG".into(), old_kubeconfig);
+    env_vars.insert("KUBIE_KUBECONFIG".into(), Some(config_path));
+    env_vars.insert("KUBIE_NAMESPACE".into(), Some(namespace_name));
+    env_vars.insert(
+        "KUBIE_SESSION".into(),
+        Some(session_path.to_string_lossy().into_owned()),
+    );
+
+    print!("{}", shell::render_env(kind, &env_vars)?);
+    Ok(())
+}
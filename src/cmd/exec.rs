@@ -4,12 +4,15 @@ use std::thread;
 use anyhow::{anyhow, Result};
 use signal_hook::consts::signal::*;
 use signal_hook::iterator::Signals;
+use skim::SkimOptions;
 
+use crate::cmd::select_contexts_and_namespaces;
 use crate::kubeconfig::{self, KubeConfig};
+use crate::selector::Selector;
 use crate::settings::{ContextHeaderBehavior, Settings};
 use crate::vars;
 
-fn run_in_context(kubeconfig: &KubeConfig, args: &[String]) -> anyhow::Result<i32> {
+fn run_in_context(settings: &Settings, kubeconfig: &KubeConfig, args: &[String]) -> anyhow::Result<i32> {
     let temp_config_file = tempfile::Builder::new()
         .prefix("kubie-config")
         .suffix(".yaml")
@@ -22,8 +25,12 @@ fn run_in_context(kubeconfig: &KubeConfig, args: &[String]) -> anyhow::Result<i3
     let mut signals = Signals::new([SIGHUP, SIGTERM, SIGINT, SIGQUIT, SIGWINCH, SIGUSR1, SIGUSR2])
         .expect("could not install signal handler");
 
+    let context_name = kubeconfig.current_context.as_deref().unwrap_or("");
+    let namespace_name = kubeconfig.contexts[0].context.namespace.as_deref().unwrap_or("default");
+
     let mut child = Command::new(&args[0])
         .args(&args[1..])
+        .envs(settings.context_env(context_name, namespace_name))
         .env("KUBECONFIG", temp_config_file.path())
         .env("KUBIE_KUBECONFIG", temp_config_file.path())
         .env("KUBIE_ACTIVE", "1")
@@ -47,34 +54,57 @@ fn run_in_context(kubeconfig: &KubeConfig, args: &[String]) -> anyhow::Result<i3
 
 pub fn exec(
     settings: &Settings,
-    context_name: String,
-    namespace_name: String,
+    skim_options: &SkimOptions,
+    target: Option<(String, String)>,
     exit_early: bool,
     context_headers_flag: Option<ContextHeaderBehavior>,
+    selectors: Vec<String>,
     args: Vec<String>,
 ) -> Result<()> {
     if args.is_empty() {
         return Ok(());
     }
 
-    let installed = kubeconfig::get_installed_contexts(settings)?;
-    let matching = installed.get_contexts_matching(&context_name, settings.behavior.allow_multiple_context_patterns);
+    let selector = Selector::parse_all(&selectors)?;
+    let mut installed = kubeconfig::get_installed_contexts(settings)?;
 
-    if matching.is_empty() {
-        return Err(anyhow!("No context matching {}", context_name));
-    }
+    // Pairs of context and namespace in which the command is run, selected in the picker when no
+    // context and namespace are given.
+    let interactive = target.is_none();
+    let targets = if let Some((context_name, namespace_name)) = target {
+        let mut matching =
+            installed.get_contexts_matching(&context_name, settings.behavior.allow_multiple_context_patterns)?;
+        matching.retain(|c| selector.matches(&settings.context_metadata(&c.item.name).tags));
+
+        if matching.is_empty() {
+            return Err(anyhow!("No context matching {}", context_name));
+        }
+        matching
+            .into_iter()
+            .map(|c| (c.item.name.clone(), namespace_name.clone()))
+            .collect()
+    } else {
+        installed
+            .contexts
+            .retain(|c| selector.matches(&settings.context_metadata(&c.item.name).tags));
+        select_contexts_and_namespaces(settings, skim_options, &mut installed)?
+    };
 
     let print_context = context_headers_flag
         .as_ref()
         .unwrap_or(&settings.behavior.print_context_in_exec)
         .should_print_headers();
 
-    for context_src in matching {
+    for (context_name, namespace_name) in targets {
         if print_context {
-            println!("CONTEXT => {}", context_src.item.name);
+            if interactive {
+                println!("CONTEXT => {context_name} NAMESPACE => {namespace_name}");
+            } else {
+                println!("CONTEXT => {context_name}");
+            }
         }
-        let kubeconfig = installed.make_kubeconfig_for_context(&context_src.item.name, Some(&namespace_name))?;
-        let return_code = run_in_context(&kubeconfig, &args)?;
+        let kubeconfig = installed.make_kubeconfig_for_context(&context_name, Some(&namespace_name))?;
+        let return_code = run_in_context(settings, &kubeconfig, &args)?;
         if print_context {
             println!("{}", "-".repeat(20));
         }
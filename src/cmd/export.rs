@@ -1,24 +1,40 @@
+use std::time::Duration;
+
 use anyhow::{anyhow, Result};
+use skim::SkimOptions;
 
+use crate::cmd::select_contexts_and_namespaces;
+use crate::exports;
 use crate::kubeconfig;
 use crate::settings::Settings;
 
-pub fn export(settings: &Settings, context_name: String, namespace_name: String) -> Result<()> {
-    let installed = kubeconfig::get_installed_contexts(settings)?;
-    let matching = installed.get_contexts_matching(&context_name, settings.behavior.allow_multiple_context_patterns);
+pub fn export(
+    settings: &Settings,
+    skim_options: &SkimOptions,
+    target: Option<(String, String)>,
+    ttl: Option<Duration>,
+) -> Result<()> {
+    let mut installed = kubeconfig::get_installed_contexts(settings)?;
 
-    if matching.is_empty() {
-        return Err(anyhow!("No context matching {}", context_name));
-    }
+    // The contexts and namespaces are selected in the picker when none are given.
+    let targets = if let Some((context_name, namespace_name)) = target {
+        let matching =
+            installed.get_contexts_matching(&context_name, settings.behavior.allow_multiple_context_patterns)?;
+
+        if matching.is_empty() {
+            return Err(anyhow!("No context matching {}", context_name));
+        }
+        matching
+            .into_iter()
+            .map(|c| (c.item.name.clone(), namespace_name.clone()))
+            .collect()
+    } else {
+        select_contexts_and_namespaces(settings, skim_options, &mut installed)?
+    };
 
-    for context_src in matching {
-        let kubeconfig = installed.make_kubeconfig_for_context(&context_src.item.name, Some(&namespace_name))?;
-        let temp_config_file = tempfile::Builder::new()
-            .prefix("kubie-config")
-            .suffix(".yaml")
-            .tempfile()?;
-        kubeconfig.write_to_file(temp_config_file.path())?;
-        let (_, path) = temp_config_file.keep()?;
+    for (context_name, namespace_name) in targets {
+        let kubeconfig = installed.make_kubeconfig_for_context(&context_name, Some(&namespace_name))?;
+        let path = exports::create(&kubeconfig, ttl.unwrap_or(settings.behavior.export_ttl))?;
         println!("{}", path.display());
     }
 
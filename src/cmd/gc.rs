@@ -0,0 +1,10 @@
+use anyhow::Result;
+
+use crate::exports;
+
+pub fn gc() -> Result<()> {
+    for path in exports::gc()? {
+        println!("Removed {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::io::{self, IsTerminal};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use skim::SkimOptions;
+
+use crate::cmd::context;
+use crate::cmd::meta::OutputFormat;
+use crate::cmd::{pick, print_serialized, print_table, PickerItem, SerializeFormat};
+use crate::settings::Settings;
+use crate::state::{self, HistoryRecord, State};
+
+#[derive(Debug, Serialize)]
+struct HistoryRow {
+    index: usize,
+    context: String,
+    namespace: Option<String>,
+    last_used: u64,
+    count: u32,
+}
+
+/// Parse a reference to the history such as `@2`, the number of entries to go back.
+pub fn parse_ref(name: &str) -> Option<usize> {
+    name.strip_prefix('@')?.parse().ok()
+}
+
+/// Entry of the history referenced by `@N`, `@0` being the most recently used pair.
+pub fn get(state: &State, index: usize) -> Result<HistoryRecord> {
+    state
+        .history
+        .get(index)
+        .cloned()
+        .ok_or_else(|| anyhow!("There is no entry @{} in the history", index))
+}
+
+/// Namespace referenced by `@N` in the history of the context, `@0` being the most recently used
+/// namespace of the context.
+pub fn get_namespace(state: &State, context_name: &str, index: usize) -> Result<Option<String>> {
+    state
+        .history
+        .iter()
+        .filter(|r| r.context == context_name)
+        .nth(index)
+        .map(|r| r.namespace.clone())
+        .ok_or_else(|| anyhow!("There is no namespace @{} in the history of {}", index, context_name))
+}
+
+/// Time elapsed since the timestamp, in the largest unit.
+fn ago(timestamp: u64) -> String {
+    let secs = state::now().saturating_sub(timestamp);
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+fn pair(context: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(namespace) => format!("{context}/{namespace}"),
+        None => context.to_string(),
+    }
+}
+
+pub fn history(settings: &Settings, skim_options: &SkimOptions, output: Option<OutputFormat>) -> Result<()> {
+    let rows: Vec<_> = State::load()?
+        .history
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| HistoryRow {
+            index,
+            context: record.context,
+            namespace: record.namespace,
+            last_used: record.last_used,
+            count: record.count,
+        })
+        .collect();
+
+    if output.is_none() && io::stdout().is_terminal() {
+        if rows.is_empty() {
+            bail!("The history is empty");
+        }
+        let items = rows
+            .iter()
+            .map(|row| PickerItem {
+                value: row.index.to_string(),
+                display: format!(
+                    "{}  ({})",
+                    pair(&row.context, row.namespace.as_deref()),
+                    ago(row.last_used)
+                ),
+            })
+            .collect();
+        let index = match pick(settings, skim_options, items)? {
+            Some(index) => index.parse::<usize>()?,
+            None => return Ok(()),
+        };
+        let row = &rows[index];
+        return context::enter_record(settings, &row.context, row.namespace.as_deref());
+    }
+
+    let output = output.unwrap_or(OutputFormat::Table);
+    match output {
+        OutputFormat::Name => {
+            for row in rows {
+                println!("{}", pair(&row.context, row.namespace.as_deref()));
+            }
+        }
+        OutputFormat::Table | OutputFormat::Wide => print_table(
+            &["REF", "CONTEXT", "NAMESPACE", "LAST USED", "COUNT"],
+            rows.into_iter()
+                .map(|row| {
+                    vec![
+                        format!("@{}", row.index),
+                        row.context,
+                        row.namespace.unwrap_or_default(),
+                        ago(row.last_used),
+                        row.count.to_string(),
+                    ]
+                })
+                .collect(),
+        ),
+        OutputFormat::Json => print_serialized(SerializeFormat::Json, &rows)?,
+        OutputFormat::Yaml => print_serialized(SerializeFormat::Yaml, &rows)?,
+    }
+    Ok(())
+}
@@ -1,10 +1,119 @@
+use std::env;
+
 use anyhow::Result;
+use serde::Serialize;
 
-use crate::cmd::meta::{KubieInfo, KubieInfoKind};
+use crate::cmd::meta::{KubieInfo, KubieInfoKind, OutputFormat, SegmentFormat};
+use crate::cmd::{print_serialized, print_table, SerializeFormat};
 use crate::kubeconfig;
+use crate::session::{HistoryEntry, Session};
+use crate::settings::Settings;
+use crate::shell::{self, prompt, ShellKind};
 use crate::vars;
 
-pub fn info(info: KubieInfo) -> Result<()> {
+/// Everything kubie knows about the current shell. Fields are empty outside of a kubie shell.
+#[derive(Debug, Default, Serialize)]
+struct ShellInfo {
+    active: bool,
+    context: Option<String>,
+    namespace: Option<String>,
+    depth: u32,
+    shell: Option<String>,
+    session_file: Option<String>,
+    kubeconfig_file: Option<String>,
+    cluster: Option<String>,
+    server: Option<String>,
+    user: Option<String>,
+    source_file: Option<String>,
+    history: Vec<HistoryEntry>,
+}
+
+impl ShellInfo {
+    fn collect(settings: &Settings) -> Result<ShellInfo> {
+        if !vars::is_kubie_active() {
+            return Ok(ShellInfo::default());
+        }
+
+        let conf = kubeconfig::get_current_config()?;
+        let context = &conf.contexts[0];
+
+        // The kubeconfig of the shell is a copy, look for the file where the context is defined.
+        let source_file = kubeconfig::get_installed_contexts(settings).ok().and_then(|installed| {
+            installed
+                .find_context_by_name(&context.name)
+                .map(|c| c.source.display().to_string())
+        });
+
+        Ok(ShellInfo {
+            active: true,
+            context: conf.current_context.clone(),
+            namespace: Some(context.context.namespace.as_deref().unwrap_or("default").into()),
+            depth: vars::get_depth(),
+            shell: env::var("KUBIE_SHELL").ok(),
+            session_file: vars::get_session_path().map(|p| p.display().to_string()),
+            kubeconfig_file: kubeconfig::get_kubeconfig_path().ok().map(|p| p.display().to_string()),
+            cluster: Some(context.context.cluster.clone()),
+            server: conf.clusters.first().and_then(|c| c.server()).map(Into::into),
+            user: Some(context.context.user.clone()),
+            source_file,
+            history: Session::load()?.history().to_vec(),
+        })
+    }
+}
+
+/// Segment printed by `kubie prompt-segment --format json`.
+#[derive(Debug, Serialize)]
+struct PromptSegment {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+pub fn prompt_segment(settings: &Settings, format: SegmentFormat) -> Result<()> {
+    if !vars::is_kubie_active() {
+        if let SegmentFormat::Json = format {
+            println!(r#"{{"active":false}}"#);
+        }
+        return Ok(());
+    }
+
+    let conf = kubeconfig::get_current_config()?;
+    let depth = vars::get_depth();
+    match format {
+        SegmentFormat::Starship => println!("{}", prompt::render_prompt(settings, None, false, &conf, depth)),
+        SegmentFormat::P10k => println!(
+            "{}",
+            prompt::render_prompt(settings, Some(ShellKind::Zsh), false, &conf, depth)
+        ),
+        SegmentFormat::Json => {
+            let segment = PromptSegment {
+                active: true,
+                context: conf.current_context.clone(),
+                namespace: Some(
+                    conf.contexts[0]
+                        .context
+                        .namespace
+                        .as_deref()
+                        .unwrap_or("default")
+                        .into(),
+                ),
+                depth: Some(depth),
+                text: Some(prompt::render_prompt(settings, None, false, &conf, depth)),
+            };
+            println!("{}", serde_json::to_string(&segment)?);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn info(settings: &Settings, info: KubieInfo) -> Result<()> {
     match info.kind {
         KubieInfoKind::Context => {
             vars::ensure_kubie_active()?;
@@ -20,6 +129,48 @@ pub fn info(info: KubieInfo) -> Result<()> {
             vars::ensure_kubie_active()?;
             println!("{}", vars::get_depth());
         }
+        KubieInfoKind::Tags => {
+            vars::ensure_kubie_active()?;
+            let conf = kubeconfig::get_current_config()?;
+            let context_name = conf.current_context.as_deref().unwrap_or("");
+            println!("{}", settings.context_metadata(context_name).tags_string());
+        }
+        KubieInfoKind::Prompt => {
+            vars::ensure_kubie_active()?;
+            let conf = kubeconfig::get_current_config()?;
+            let shell_kind = shell::current_shell_kind();
+            print!(
+                "{}",
+                prompt::render_prompt(settings, shell_kind, true, &conf, vars::get_depth())
+            );
+        }
+        KubieInfoKind::All { output } => {
+            let info = ShellInfo::collect(settings)?;
+            match output {
+                OutputFormat::Json => print_serialized(SerializeFormat::Json, &info)?,
+                OutputFormat::Yaml => print_serialized(SerializeFormat::Yaml, &info)?,
+                OutputFormat::Name => println!("{}", info.context.as_deref().unwrap_or("")),
+                OutputFormat::Table | OutputFormat::Wide => {
+                    let or_empty = |value: Option<String>| value.unwrap_or_default();
+                    print_table(
+                        &["KEY", "VALUE"],
+                        vec![
+                            vec!["active".into(), info.active.to_string()],
+                            vec!["context".into(), or_empty(info.context)],
+                            vec!["namespace".into(), or_empty(info.namespace)],
+                            vec!["depth".into(), info.depth.to_string()],
+                            vec!["shell".into(), or_empty(info.shell)],
+                            vec!["session_file".into(), or_empty(info.session_file)],
+                            vec!["kubeconfig_file".into(), or_empty(info.kubeconfig_file)],
+                            vec!["cluster".into(), or_empty(info.cluster)],
+                            vec!["server".into(), or_empty(info.server)],
+                            vec!["user".into(), or_empty(info.user)],
+                            vec!["source_file".into(), or_empty(info.source_file)],
+                        ],
+                    );
+                }
+            }
+        }
     };
 
     Ok(())
@@ -3,7 +3,7 @@ use std::path::Path;
 
 use anyhow::Result;
 
-use crate::kubeconfig::{self, Installed};
+use crate::kubeconfig::{self, Context, Installed};
 use crate::settings::Settings;
 
 fn lint_clusters(installed: &Installed) {
@@ -62,31 +62,30 @@ fn lint_users(installed: &Installed) {
     }
 }
 
+/// Check that the cluster and user referenced by a context can be found, returning a description
+/// of every dangling reference.
+pub fn check_context_references(installed: &Installed, context: &Context, source: &Path) -> Vec<String> {
+    let mut issues = vec![];
+    if installed.find_cluster_by_name(&context.cluster, source).is_none() {
+        issues.push(format!("unknown cluster '{}'", context.cluster));
+    }
+    if installed.find_user_by_name(&context.user, source).is_none() {
+        issues.push(format!("unknown user '{}'", context.user));
+    }
+    issues
+}
+
 fn lint_contexts(installed: &Installed) {
     let mut set = HashSet::new();
 
     for context_src in &installed.contexts {
         let named = &context_src.item;
 
-        if installed
-            .find_cluster_by_name(&named.context.cluster, &context_src.source)
-            .is_none()
-        {
-            println!(
-                "Context '{}' references unknown cluster '{}' in file {}",
-                named.name,
-                named.context.cluster,
-                context_src.source.display(),
-            );
-        }
-        if installed
-            .find_user_by_name(&named.context.user, &context_src.source)
-            .is_none()
-        {
+        for issue in check_context_references(installed, &named.context, &context_src.source) {
             println!(
-                "Context '{}' references unknown users '{}' in file {}",
+                "Context '{}' references {} in file {}",
                 named.name,
-                named.context.user,
+                issue,
                 context_src.source.display(),
             );
         }
@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::cmd::meta::{ListKind, OutputFormat};
+use crate::cmd::{print_serialized, print_table, SerializeFormat};
+use crate::kubeconfig::{self, Installed};
+use crate::settings::Settings;
+use crate::state::State;
+
+#[derive(Debug, Serialize)]
+struct ContextRow {
+    name: String,
+    cluster: String,
+    server: Option<String>,
+    user: String,
+    auth_type: Option<String>,
+    default_namespace: Option<String>,
+    remembered_namespace: Option<String>,
+    source: String,
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ClusterRow {
+    name: String,
+    server: Option<String>,
+    contexts: Vec<String>,
+    source: String,
+}
+
+#[derive(Debug, Serialize)]
+struct UserRow {
+    name: String,
+    auth_type: String,
+    contexts: Vec<String>,
+    source: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportRow {
+    path: String,
+    context: String,
+    namespace: String,
+    expires: String,
+    expired: bool,
+    owner: Option<u32>,
+    in_use: bool,
+}
+
+/// Names of the contexts matching the pattern of `--match`, `None` without a pattern.
+fn matching_context_names(
+    settings: &Settings,
+    installed: &Installed,
+    pattern: Option<&str>,
+) -> Result<Option<HashSet<String>>> {
+    let Some(pattern) = pattern else {
+        return Ok(None);
+    };
+    let contexts = installed.get_contexts_matching(pattern, settings.behavior.allow_multiple_context_patterns)?;
+    Ok(Some(contexts.into_iter().map(|c| c.item.name.clone()).collect()))
+}
+
+fn context_rows(settings: &Settings, installed: &Installed, pattern: Option<&str>) -> Result<Vec<ContextRow>> {
+    let state = State::load()?;
+
+    let mut contexts = match pattern {
+        Some(pattern) => installed.get_contexts_matching(pattern, settings.behavior.allow_multiple_context_patterns)?,
+        None => installed.contexts.iter().collect(),
+    };
+    contexts.sort_by_key(|c| &c.item.name);
+
+    Ok(contexts
+        .into_iter()
+        .map(|context_src| {
+            let named = &context_src.item;
+            let cluster = installed.find_cluster_by_name(&named.context.cluster, &context_src.source);
+            let user = installed.find_user_by_name(&named.context.user, &context_src.source);
+            ContextRow {
+                name: named.name.clone(),
+                cluster: named.context.cluster.clone(),
+                server: cluster.and_then(|c| c.item.server()).map(Into::into),
+                user: named.context.user.clone(),
+                auth_type: user.map(|u| u.item.auth_type()),
+                default_namespace: named.context.namespace.clone(),
+                remembered_namespace: state.namespace_history.get(&named.name).cloned().flatten(),
+                source: context_src.source.display().to_string(),
+                tags: settings.context_metadata(&named.name).tags,
+            }
+        })
+        .collect())
+}
+
+fn ls_contexts(settings: &Settings, installed: &Installed, pattern: Option<&str>, output: OutputFormat) -> Result<()> {
+    let rows = context_rows(settings, installed, pattern)?;
+    let or_empty = |value: &Option<String>| value.clone().unwrap_or_default();
+
+    match output {
+        OutputFormat::Name => {
+            for row in rows {
+                println!("{}", row.name);
+            }
+        }
+        OutputFormat::Table => print_table(
+            &["NAME", "CLUSTER", "USER", "NAMESPACE"],
+            rows.iter()
+                .map(|row| {
+                    let namespace = row.remembered_namespace.as_ref().or(row.default_namespace.as_ref());
+                    vec![
+                        row.name.clone(),
+                        row.cluster.clone(),
+                        row.user.clone(),
+                        namespace.cloned().unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        ),
+        OutputFormat::Wide => print_table(
+            &[
+                "NAME",
+                "CLUSTER",
+                "SERVER",
+                "USER",
+                "AUTH",
+                "DEFAULT NS",
+                "REMEMBERED NS",
+                "SOURCE",
+                "TAGS",
+            ],
+            rows.iter()
+                .map(|row| {
+                    vec![
+                        row.name.clone(),
+                        row.cluster.clone(),
+                        or_empty(&row.server),
+                        row.user.clone(),
+                        or_empty(&row.auth_type),
+                        or_empty(&row.default_namespace),
+                        or_empty(&row.remembered_namespace),
+                        row.source.clone(),
+                        row.tags
+                            .iter()
+                            .map(|(k, v)| format!("{k}={v}"))
+                            .collect::<Vec<_>>()
+                            .join(","),
+                    ]
+                })
+                .collect(),
+        ),
+        OutputFormat::Json => print_serialized(SerializeFormat::Json, &rows)?,
+        OutputFormat::Yaml => print_serialized(SerializeFormat::Yaml, &rows)?,
+    }
+    Ok(())
+}
+
+/// With `--match`, only the clusters of the matching contexts are listed, along with these contexts.
+fn ls_clusters(settings: &Settings, installed: &Installed, pattern: Option<&str>, output: OutputFormat) -> Result<()> {
+    let matching = matching_context_names(settings, installed, pattern)?;
+    let mut rows: Vec<_> = installed
+        .clusters
+        .iter()
+        .map(|cluster_src| ClusterRow {
+            name: cluster_src.item.name.clone(),
+            server: cluster_src.item.server().map(Into::into),
+            contexts: installed
+                .find_contexts_by_cluster(&cluster_src.item.name, &cluster_src.source)
+                .iter()
+                .map(|c| c.item.name.clone())
+                .filter(|name| matching.as_ref().is_none_or(|m| m.contains(name)))
+                .collect(),
+            source: cluster_src.source.display().to_string(),
+        })
+        .collect();
+    if matching.is_some() {
+        rows.retain(|row| !row.contexts.is_empty());
+    }
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+
+    match output {
+        OutputFormat::Name => {
+            for row in rows {
+                println!("{}", row.name);
+            }
+        }
+        OutputFormat::Table | OutputFormat::Wide => {
+            let mut headers = vec!["NAME", "SERVER", "CONTEXTS"];
+            if let OutputFormat::Wide = output {
+                headers.push("SOURCE");
+            }
+            print_table(
+                &headers,
+                rows.into_iter()
+                    .map(|row| {
+                        vec![
+                            row.name,
+                            row.server.unwrap_or_default(),
+                            row.contexts.join(","),
+                            row.source,
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+        OutputFormat::Json => print_serialized(SerializeFormat::Json, &rows)?,
+        OutputFormat::Yaml => print_serialized(SerializeFormat::Yaml, &rows)?,
+    }
+    Ok(())
+}
+
+/// With `--match`, only the users of the matching contexts are listed, along with these contexts.
+fn ls_users(settings: &Settings, installed: &Installed, pattern: Option<&str>, output: OutputFormat) -> Result<()> {
+    let matching = matching_context_names(settings, installed, pattern)?;
+    let mut rows: Vec<_> = installed
+        .users
+        .iter()
+        .map(|user_src| UserRow {
+            name: user_src.item.name.clone(),
+            auth_type: user_src.item.auth_type(),
+            contexts: installed
+                .find_contexts_by_user(&user_src.item.name, &user_src.source)
+                .iter()
+                .map(|c| c.item.name.clone())
+                .filter(|name| matching.as_ref().is_none_or(|m| m.contains(name)))
+                .collect(),
+            source: user_src.source.display().to_string(),
+        })
+        .collect();
+    if matching.is_some() {
+        rows.retain(|row| !row.contexts.is_empty());
+    }
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+
+    match output {
+        OutputFormat::Name => {
+            for row in rows {
+                println!("{}", row.name);
+            }
+        }
+        OutputFormat::Table | OutputFormat::Wide => {
+            let mut headers = vec!["NAME", "AUTH", "CONTEXTS"];
+            if let OutputFormat::Wide = output {
+                headers.push("SOURCE");
+            }
+            print_table(
+                &headers,
+                rows.into_iter()
+                    .map(|row| vec![row.name, row.auth_type, row.contexts.join(","), row.source])
+                    .collect(),
+            )
+        }
+        OutputFormat::Json => print_serialized(SerializeFormat::Json, &rows)?,
+        OutputFormat::Yaml => print_serialized(SerializeFormat::Yaml, &rows)?,
+    }
+    Ok(())
+}
+
+/// With `--match`, only the exports of the matching contexts are listed.
+fn ls_exports(settings: &Settings, pattern: Option<&str>, output: OutputFormat) -> Result<()> {
+    let matching = match pattern {
+        Some(_) => matching_context_names(settings, &kubeconfig::get_installed_contexts(settings)?, pattern)?,
+        None => None,
+    };
+    let state = State::load()?;
+    let rows: Vec<_> = state
+        .exports
+        .into_iter()
+        .filter(|(_, entry)| matching.as_ref().is_none_or(|m| m.contains(&entry.context)))
+        .map(|(path, entry)| ExportRow {
+            expires: humantime::format_rfc3339_seconds(entry.expires_at()).to_string(),
+            expired: entry.is_expired(),
+            in_use: entry.is_in_use(),
+            owner: entry.owner,
+            context: entry.context,
+            namespace: entry.namespace,
+            path,
+        })
+        .collect();
+
+    match output {
+        OutputFormat::Name => {
+            for row in rows {
+                println!("{}", row.path);
+            }
+        }
+        OutputFormat::Table | OutputFormat::Wide => {
+            let mut headers = vec!["PATH", "CONTEXT", "NAMESPACE", "EXPIRES", "IN USE"];
+            if let OutputFormat::Wide = output {
+                headers.push("OWNER");
+            }
+            print_table(
+                &headers,
+                rows.into_iter()
+                    .map(|row| {
+                        vec![
+                            row.path,
+                            row.context,
+                            row.namespace,
+                            row.expires,
+                            if row.in_use { "yes" } else { "no" }.into(),
+                            row.owner.map(|pid| pid.to_string()).unwrap_or_default(),
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+        OutputFormat::Json => print_serialized(SerializeFormat::Json, &rows)?,
+        OutputFormat::Yaml => print_serialized(SerializeFormat::Yaml, &rows)?,
+    }
+    Ok(())
+}
+
+pub fn ls(settings: &Settings, kind: ListKind, output: OutputFormat, pattern: Option<String>) -> Result<()> {
+    match kind {
+        ListKind::Contexts => {
+            let installed = kubeconfig::get_installed_contexts(settings)?;
+            ls_contexts(settings, &installed, pattern.as_deref(), output)
+        }
+        ListKind::Clusters => {
+            let installed = kubeconfig::get_installed_contexts(settings)?;
+            ls_clusters(settings, &installed, pattern.as_deref(), output)
+        }
+        ListKind::Users => {
+            let installed = kubeconfig::get_installed_contexts(settings)?;
+            ls_users(settings, &installed, pattern.as_deref(), output)
+        }
+        ListKind::Exports => ls_exports(settings, pattern.as_deref(), output),
+    }
+}
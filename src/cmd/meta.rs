@@ -1,7 +1,14 @@
-use clap::{CommandFactory, Parser};
-use clap_complete::{generate, Shell};
+use std::io;
+use std::time::Duration;
 
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use clap_complete::env::Shells;
+use clap_complete::{ArgValueCandidates, Shell};
+
+use crate::cmd::complete;
 use crate::settings::ContextHeaderBehavior;
+use crate::shell::ShellKind;
 
 #[derive(Debug, Parser)]
 #[clap(version)]
@@ -11,7 +18,7 @@ pub enum Kubie {
     #[clap(name = "ctx")]
     Context {
         /// Specify in which namespace of the context the shell is spawned.
-        #[clap(short = 'n', long = "namespace")]
+        #[clap(short = 'n', long = "namespace", add = ArgValueCandidates::new(complete::namespaces))]
         namespace_name: Option<String>,
 
         /// Specify files from which to load contexts instead of using the installed ones.
@@ -22,7 +29,18 @@ pub enum Kubie {
         #[clap(short = 'r', long = "recursive")]
         recursive: bool,
 
-        /// Name of the context to enter. Use '-' to switch back to the previous context.
+        /// Only list contexts whose tags match the selector, e.g. `env=staging`. Can be repeated.
+        #[clap(short = 't', long = "tag")]
+        tags: Vec<String>,
+
+        /// Only list the contexts pinned with `kubie pin`.
+        #[clap(short = 'p', long = "pinned")]
+        pinned: bool,
+
+        /// Name of the context to enter. Use '-' to switch back to the previous context, or '@N' to
+        /// enter the Nth most recently used context and namespace listed by `kubie history`. Aliases
+        /// defined in the settings can be used instead of the context's name.
+        #[clap(add = ArgValueCandidates::new(complete::contexts_and_aliases))]
         context_name: Option<String>,
     },
 
@@ -38,28 +56,77 @@ pub enum Kubie {
         #[clap(short = 'u', long = "unset")]
         unset: bool,
 
-        /// Name of the namespace to enter. Use '-' to switch back to the previous namespace.
+        /// Name of the namespace to enter. Use '-' to switch back to the previous namespace, or '@N'
+        /// to enter the Nth most recently used namespace of the context.
+        #[clap(add = ArgValueCandidates::new(complete::namespaces))]
         namespace_name: Option<String>,
     },
 
+    /// Pin a context, or a namespace of a context, to list it first in the picker. Lists the pinned
+    /// contexts and namespaces when no name is given.
+    #[clap(name = "pin")]
+    Pin {
+        /// Name of the context, or `<context>/<namespace>` to pin a namespace of the context.
+        #[clap(add = ArgValueCandidates::new(complete::contexts))]
+        name: Option<String>,
+    },
+
+    /// Unpin a context, or a namespace of a context.
+    #[clap(name = "unpin")]
+    Unpin {
+        /// Name of the context, or `<context>/<namespace>` to unpin a namespace of the context.
+        #[clap(add = ArgValueCandidates::new(complete::pinned))]
+        name: String,
+    },
+
+    /// Pick a context and namespace among the most recently used ones, or list them when the output
+    /// is not a terminal. Entries can be entered with `kubie ctx @N`.
+    #[clap(name = "history")]
+    History {
+        /// Output format. Lists the entries instead of opening the picker.
+        #[clap(value_enum, short = 'o', long = "output")]
+        output: Option<OutputFormat>,
+    },
+
     /// View info about the current kubie shell, such as the context name and the
     /// current namespace.
     #[clap(name = "info")]
     Info(KubieInfo),
 
+    /// Print the kubie segment of the current shell for prompt frameworks such as starship or
+    /// powerlevel10k. Prints nothing outside of a kubie shell.
+    #[clap(name = "prompt-segment")]
+    PromptSegment {
+        /// Format of the segment.
+        #[clap(value_enum, long = "format", default_value = "starship")]
+        format: SegmentFormat,
+    },
+
     /// Execute a command inside of the given context and namespace.
     #[clap(name = "exec", trailing_var_arg = true)]
     Exec {
-        /// Name of the context in which to run the command.
-        context_name: String,
+        /// Name of the context in which to run the command. Wildcards, negations (`!*-dev`), regexes
+        /// (`re:^prod-(eu|us)$`) and fields (`server=*.eks.amazonaws.com`, `cluster=`, `user=`) can be used
+        /// to run the command in multiple contexts. Use `kubie ls --match` to preview the contexts.
+        #[clap(required_unless_present = "interactive", add = ArgValueCandidates::new(complete::contexts))]
+        context_name: Option<String>,
         /// Namespace in which to run the command. This is mandatory to avoid potential errors.
-        namespace_name: String,
+        #[clap(required_unless_present = "interactive", add = ArgValueCandidates::new(complete::namespaces))]
+        namespace_name: Option<String>,
+        /// Select the contexts and then the namespaces in which to run the command in a menu
+        /// allowing multiple selection. The context and namespace are omitted, e.g.
+        /// `kubie exec -i -- kubectl get pods`.
+        #[clap(short = 'i', long = "interactive")]
+        interactive: bool,
         /// Exit early if a command fails when using a wildcard context.
         #[clap(short = 'e', long = "exit-early")]
         exit_early: bool,
         /// Overrides behavior.print_context_in_exec in Kubie settings file.
         #[clap(value_enum, long = "context-headers")]
         context_headers_flag: Option<ContextHeaderBehavior>,
+        /// Only run in the matching contexts whose tags match the selector, e.g. `env=prod,region!=us`.
+        #[clap(short = 's', long = "selector")]
+        selectors: Vec<String>,
         /// Command to run as well as its arguments.
         args: Vec<String>,
     },
@@ -68,22 +135,143 @@ pub enum Kubie {
     #[clap(name = "export")]
     Export {
         /// Name of the context to export.
-        context_name: String,
+        #[clap(required_unless_present = "interactive", add = ArgValueCandidates::new(complete::contexts))]
+        context_name: Option<String>,
+        /// Name of the namespace in the context. This is mandatory to avoid potential errors.
+        #[clap(required_unless_present = "interactive", add = ArgValueCandidates::new(complete::namespaces))]
+        namespace_name: Option<String>,
+        /// Select the contexts and then the namespaces to export in a menu allowing multiple
+        /// selection. A file is exported for each pair.
+        #[clap(short = 'i', long = "interactive", conflicts_with_all = ["context_name", "namespace_name"])]
+        interactive: bool,
+        /// Time after which the file is removed once the process which called kubie has exited,
+        /// e.g. `30m`. Overrides behavior.export_ttl in Kubie settings file.
+        #[clap(long = "ttl", value_parser = humantime::parse_duration)]
+        ttl: Option<Duration>,
+    },
+
+    /// Print the statements which export an isolated configuration file for a context and namespace
+    /// in the current shell, without spawning a shell. Use `eval "$(kubie env <ctx> <ns>)"`.
+    #[clap(name = "env")]
+    Env {
+        /// Name of the context to export.
+        #[clap(required_unless_present = "unset", add = ArgValueCandidates::new(complete::contexts))]
+        context_name: Option<String>,
         /// Name of the namespace in the context. This is mandatory to avoid potential errors.
-        namespace_name: String,
+        #[clap(required_unless_present = "unset", add = ArgValueCandidates::new(complete::namespaces))]
+        namespace_name: Option<String>,
+        /// Shell syntax of the statements. Determined automatically if omitted.
+        #[clap(value_enum, long = "shell")]
+        shell: Option<ShellKind>,
+        /// Time after which the configuration file is removed once the shell has exited, e.g. `30m`.
+        /// Overrides behavior.export_ttl in Kubie settings file.
+        #[clap(long = "ttl", value_parser = humantime::parse_duration)]
+        ttl: Option<Duration>,
+        /// Print the statements which unset the variables and remove the configuration file.
+        #[clap(long = "unset", conflicts_with_all = ["context_name", "namespace_name"])]
+        unset: bool,
+    },
+
+    /// List the contexts, clusters or users found in the Kubernetes config files.
+    #[clap(name = "ls")]
+    List {
+        /// Kind of entries to list.
+        #[clap(value_enum, default_value = "contexts")]
+        kind: ListKind,
+        /// Output format.
+        #[clap(value_enum, short = 'o', long = "output", default_value = "table")]
+        output: OutputFormat,
+        /// Only list the contexts matching the pattern, using the same syntax as `kubie exec`, or the
+        /// clusters, users and exports of these contexts.
+        #[clap(short = 'm', long = "match")]
+        pattern: Option<String>,
     },
 
     /// Check the Kubernetes config files for issues.
     #[clap(name = "lint")]
     Lint,
 
+    /// Remove the files written by `kubie export` and `kubie env` whose TTL has expired and which
+    /// are not in use anymore. Other commands also do this automatically.
+    #[clap(name = "gc")]
+    Gc,
+
+    /// Diagnose kubie's integration with the environment.
+    #[clap(name = "doctor")]
+    Doctor {
+        #[clap(subcommand)]
+        kind: DoctorKind,
+    },
+
     /// Edit the given context.
     #[clap(name = "edit")]
     Edit {
         /// Name of the context to edit.
+        #[clap(add = ArgValueCandidates::new(complete::contexts))]
         context_name: Option<String>,
     },
 
+    /// Change the cluster, user or default namespace of a context in the file where it is defined.
+    #[clap(name = "set-context")]
+    SetContext {
+        /// Name of the context to modify.
+        #[clap(add = ArgValueCandidates::new(complete::contexts))]
+        context_name: String,
+        /// File to modify when the context is defined in more than one file.
+        #[clap(short = 'f', long = "kubeconfig")]
+        kubeconfig: Option<String>,
+        /// Namespace stored in the kubeconfig file, used when no namespace was remembered by kubie.
+        #[clap(short = 'n', long = "namespace", conflicts_with = "unset_namespace", add = ArgValueCandidates::new(complete::namespaces))]
+        namespace_name: Option<String>,
+        /// Remove the namespace stored in the kubeconfig file.
+        #[clap(long = "unset-namespace")]
+        unset_namespace: bool,
+        /// Name of the cluster the context should reference.
+        #[clap(long = "cluster")]
+        cluster_name: Option<String>,
+        /// Name of the user the context should reference.
+        #[clap(long = "user")]
+        user_name: Option<String>,
+    },
+
+    /// Change the connection settings of a cluster in the file where it is defined.
+    #[clap(name = "set-cluster")]
+    SetCluster {
+        /// Name of the cluster to modify.
+        cluster_name: String,
+        /// File to modify when the cluster is defined in more than one file.
+        #[clap(short = 'f', long = "kubeconfig")]
+        kubeconfig: Option<String>,
+        /// Address of the Kubernetes API server.
+        #[clap(long = "server")]
+        server: Option<String>,
+        /// Path to the certificate authority file.
+        #[clap(long = "certificate-authority")]
+        certificate_authority: Option<String>,
+        /// Skip the validation of the server's certificate.
+        #[clap(long = "insecure-skip-tls-verify")]
+        insecure_skip_tls_verify: Option<bool>,
+    },
+
+    /// Change the credentials of a user in the file where it is defined.
+    #[clap(name = "set-user")]
+    SetUser {
+        /// Name of the user to modify.
+        user_name: String,
+        /// File to modify when the user is defined in more than one file.
+        #[clap(short = 'f', long = "kubeconfig")]
+        kubeconfig: Option<String>,
+        /// Bearer token used to authenticate.
+        #[clap(long = "token")]
+        token: Option<String>,
+        /// Path to the client certificate file.
+        #[clap(long = "client-certificate")]
+        client_certificate: Option<String>,
+        /// Path to the client key file.
+        #[clap(long = "client-key")]
+        client_key: Option<String>,
+    },
+
     /// Edit kubie's config file.
     #[clap(name = "edit-config")]
     EditConfig,
@@ -99,12 +287,14 @@ pub enum Kubie {
     #[clap(name = "delete")]
     Delete {
         /// Name of the context to edit.
+        #[clap(add = ArgValueCandidates::new(complete::contexts))]
         context_name: Option<String>,
     },
 
     /// Generate a completion script. Enable completion using
     /// `source <(kubie generate-completion)`. This can be added to your shell's
-    /// configuration file to enable completion automatically.
+    /// configuration file to enable completion automatically. Context and namespace
+    /// names are completed by kubie itself.
     #[clap(name = "generate-completion")]
     GenerateCompletion(GenerateCompletionCommand),
 }
@@ -127,6 +317,60 @@ pub enum KubieInfoKind {
     /// Get the current depth of contexts.
     #[clap(name = "depth")]
     Depth,
+    /// Get the tags of the current shell's context.
+    #[clap(name = "tags")]
+    Tags,
+    /// Get the prompt segment of the current shell, as rendered in kubie's prompt.
+    #[clap(name = "prompt")]
+    Prompt,
+    /// Get all the details about the current shell in a single call, for editor plugins and status bars.
+    #[clap(name = "all")]
+    All {
+        /// Output format.
+        #[clap(value_enum, short = 'o', long = "output", default_value = "json")]
+        output: OutputFormat,
+    },
+}
+
+/// Part of the environment diagnosed by `kubie doctor`.
+#[derive(Debug, Parser)]
+pub enum DoctorKind {
+    /// Print kubie's process ancestry and which shell kubie spawns.
+    #[clap(name = "shell")]
+    Shell,
+}
+
+/// Kind of entries listed by `kubie ls`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum ListKind {
+    Contexts,
+    Clusters,
+    Users,
+    Exports,
+}
+
+/// Format of the segment printed by `kubie prompt-segment`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum SegmentFormat {
+    /// Plain text rendered from the prompt template, for a starship custom module.
+    Starship,
+    /// Plain text rendered from the prompt template with `%` escaped, for `p10k segment -e`.
+    P10k,
+    /// JSON object with the context, namespace, depth and rendered text.
+    Json,
+}
+
+/// Output format of the commands which print structured data.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum OutputFormat {
+    Table,
+    Wide,
+    Json,
+    Yaml,
+    Name,
 }
 
 #[derive(Debug, Parser)]
@@ -136,12 +380,17 @@ pub struct GenerateCompletionCommand {
     pub shell: Option<Shell>,
 }
 
-/// Generate a completion script.
-pub fn generate_completion(command: GenerateCompletionCommand) {
-    let mut app = Kubie::command();
+/// Generate a completion script. The script calls back into kubie with the `COMPLETE` variable set
+/// to complete the command line, so that context and namespace names are completed too.
+pub fn generate_completion(command: GenerateCompletionCommand) -> Result<()> {
     let bin_name = env!("CARGO_BIN_NAME");
-    let shell = determine_shell(command);
-    generate(shell, &mut app, bin_name, &mut std::io::stdout());
+    let shell = determine_shell(command).to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell)
+        .ok_or_else(|| anyhow!("Dynamic completion is not supported for {}", shell))?;
+    completer.write_registration("COMPLETE", bin_name, bin_name, bin_name, &mut io::stdout())?;
+    Ok(())
 }
 
 fn determine_shell(command: GenerateCompletionCommand) -> Shell {
@@ -1,21 +1,36 @@
-use std::io::{self, Cursor, IsTerminal};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal};
+use std::sync::Arc;
 
-use anyhow::{bail, Result};
-use skim::prelude::{Key, SkimItemReader};
-use skim::{Skim, SkimOptions};
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use skim::prelude::{unbounded, Key, SkimItemReceiver, SkimItemSender};
+use skim::{Skim, SkimItem, SkimOptions};
 
 use crate::kubeconfig::Installed;
 use crate::kubectl;
+use crate::settings::{PickerBackend, PickerSort, Settings};
+use crate::state::State;
 
+pub mod complete;
 pub mod context;
 pub mod delete;
+pub mod doctor;
 pub mod edit;
+pub mod env;
 pub mod exec;
 pub mod export;
+pub mod gc;
+pub mod history;
 pub mod info;
 pub mod lint;
+pub mod ls;
 pub mod meta;
 pub mod namespace;
+mod picker;
+pub mod pin;
+pub mod set;
 #[cfg(feature = "update")]
 pub mod update;
 
@@ -25,9 +40,132 @@ pub enum SelectResult {
     Selected(String),
 }
 
-pub fn select_or_list_context(skim_options: &SkimOptions, installed: &mut Installed) -> Result<SelectResult> {
+/// Entry shown in the picker. The display text is used for matching and the value is what gets
+/// selected.
+struct PickerItem {
+    value: String,
+    display: String,
+}
+
+/// Item of the picker along with its position, which skim uses to tell the selected items apart.
+struct IndexedItem(usize, PickerItem);
+
+impl SkimItem for IndexedItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.1.display)
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.1.value)
+    }
+
+    fn get_index(&self) -> usize {
+        self.0
+    }
+}
+
+/// Open the picker over the given items, returning the values of the selected items. More than one
+/// item can only be selected when multiple selection is enabled in the options.
+fn pick_many(settings: &Settings, skim_options: &SkimOptions, items: Vec<PickerItem>) -> Result<Vec<String>> {
+    match settings.picker.backend {
+        PickerBackend::Skim => Ok(pick_skim(skim_options, items)),
+        PickerBackend::Fzf => picker::run(
+            picker::fzf_command(skim_options.multi, skim_options.query.as_deref()),
+            items,
+        ),
+        PickerBackend::Command => {
+            let command = settings
+                .picker
+                .command
+                .as_deref()
+                .ok_or_else(|| anyhow!("picker.command must be set to use the command picker backend"))?;
+            picker::run(picker::custom_command(command, skim_options.query.as_deref())?, items)
+        }
+    }
+}
+
+/// Open the picker over the given items, returning the value of the selected item.
+fn pick(settings: &Settings, skim_options: &SkimOptions, items: Vec<PickerItem>) -> Result<Option<String>> {
+    Ok(pick_many(settings, skim_options, items)?.into_iter().next())
+}
+
+fn pick_skim(skim_options: &SkimOptions, mut items: Vec<PickerItem>) -> Vec<String> {
+    // NOTE: skim shows the list of items in reverse order
+    items.reverse();
+    let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+    for (index, item) in items.into_iter().enumerate() {
+        let _ = tx.send(Arc::new(IndexedItem(index, item)));
+    }
+    drop(tx);
+
+    let selected_items = Skim::run_with(skim_options, Some(rx))
+        .map(|out| match out.final_key {
+            Key::Enter => out.selected_items,
+            _ => Vec::new(),
+        })
+        .unwrap_or_default();
+    selected_items.iter().map(|item| item.output().to_string()).collect()
+}
+
+/// Text shown in the picker for a context, including its tags, description and owner when set.
+fn context_display(settings: &Settings, context_name: &str) -> String {
+    let metadata = settings.context_metadata(context_name);
+    let mut display = context_name.to_string();
+    if !metadata.tags.is_empty() {
+        display.push_str(&format!("  [{}]", metadata.tags_string()));
+    }
+    if let Some(description) = &metadata.description {
+        display.push_str(&format!("  {description}"));
+    }
+    if let Some(owner) = &metadata.owner {
+        display.push_str(&format!("  ({owner})"));
+    }
+    display
+}
+
+/// Mark the pinned entries of the picker.
+fn pinned_display(pinned: bool, display: String) -> String {
+    if pinned {
+        format!("★ {display}")
+    } else {
+        display
+    }
+}
+
+/// Sort the names by decreasing score. The sort is stable, names with the same score keep their order.
+fn sort_by_score(names: &mut [String], score: impl Fn(&str) -> f64) {
+    names.sort_by(|a, b| score(b).total_cmp(&score(a)));
+}
+
+/// Names of the contexts in the order of the picker: pinned first, then by frecency when enabled,
+/// then alphabetically.
+fn sorted_context_names(settings: &Settings, state: &State, installed: &mut Installed) -> Vec<String> {
     installed.contexts.sort_by(|a, b| a.item.name.cmp(&b.item.name));
     let mut context_names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.clone()).collect();
+    if settings.picker.sort == PickerSort::Frecency {
+        sort_by_score(&mut context_names, |name| state.context_frecency(name));
+    }
+    context_names.sort_by_key(|name| !state.pinned_contexts.contains(name));
+    context_names
+}
+
+fn context_items(settings: &Settings, state: &State, context_names: Vec<String>) -> Vec<PickerItem> {
+    context_names
+        .into_iter()
+        .map(|name| PickerItem {
+            display: pinned_display(state.pinned_contexts.contains(&name), context_display(settings, &name)),
+            value: name,
+        })
+        .collect()
+}
+
+pub fn select_or_list_context(
+    settings: &Settings,
+    skim_options: &SkimOptions,
+    installed: &mut Installed,
+) -> Result<SelectResult> {
+    let state = State::load()?;
+    let context_names = sorted_context_names(settings, &state, installed);
 
     if context_names.is_empty() {
         bail!("No contexts found");
@@ -37,20 +175,10 @@ pub fn select_or_list_context(skim_options: &SkimOptions, installed: &mut Instal
     }
 
     if io::stdout().is_terminal() {
-        // NOTE: skim shows the list of context names in reverse order
-        context_names.reverse();
-        let item_reader = SkimItemReader::default();
-        let items = item_reader.of_bufread(Cursor::new(context_names.join("\n")));
-        let selected_items = Skim::run_with(skim_options, Some(items))
-            .map(|out| match out.final_key {
-                Key::Enter => out.selected_items,
-                _ => Vec::new(),
-            })
-            .unwrap_or_default();
-        if selected_items.is_empty() {
-            return Ok(SelectResult::Cancelled);
+        match pick(settings, skim_options, context_items(settings, &state, context_names))? {
+            Some(name) => Ok(SelectResult::Selected(name)),
+            None => Ok(SelectResult::Cancelled),
         }
-        Ok(SelectResult::Selected(selected_items[0].output().to_string()))
     } else {
         for c in context_names {
             println!("{c}");
@@ -59,30 +187,35 @@ pub fn select_or_list_context(skim_options: &SkimOptions, installed: &mut Instal
     }
 }
 
-pub fn select_or_list_namespace(skim_options: &SkimOptions, namespaces: Option<Vec<String>>) -> Result<SelectResult> {
-    let mut namespaces = namespaces.unwrap_or_else(|| kubectl::get_namespaces(None).expect("could not get namespaces"));
-
+pub fn select_or_list_namespace(
+    settings: &Settings,
+    skim_options: &SkimOptions,
+    context_name: &str,
+    mut namespaces: Vec<String>,
+) -> Result<SelectResult> {
     namespaces.sort();
+    let state = State::load()?;
+    if settings.picker.sort == PickerSort::Frecency {
+        sort_by_score(&mut namespaces, |name| state.namespace_frecency(context_name, name));
+    }
+    namespaces.sort_by_key(|name| !state.is_namespace_pinned(context_name, name));
 
     if namespaces.is_empty() {
         bail!("No namespaces found");
     }
 
     if io::stdout().is_terminal() {
-        // NOTE: skim shows the list of namespaces in reverse order
-        namespaces.reverse();
-        let item_reader = SkimItemReader::default();
-        let items = item_reader.of_bufread(Cursor::new(namespaces.join("\n")));
-        let selected_items = Skim::run_with(skim_options, Some(items))
-            .map(|out| match out.final_key {
-                Key::Enter => out.selected_items,
-                _ => Vec::new(),
+        let items = namespaces
+            .into_iter()
+            .map(|name| PickerItem {
+                display: pinned_display(state.is_namespace_pinned(context_name, &name), name.clone()),
+                value: name,
             })
-            .unwrap_or_default();
-        if selected_items.is_empty() {
-            return Ok(SelectResult::Cancelled);
+            .collect();
+        match pick(settings, skim_options, items)? {
+            Some(name) => Ok(SelectResult::Selected(name)),
+            None => Ok(SelectResult::Cancelled),
         }
-        Ok(SelectResult::Selected(selected_items[0].output().to_string()))
     } else {
         for n in namespaces {
             println!("{n}");
@@ -90,3 +223,121 @@ pub fn select_or_list_namespace(skim_options: &SkimOptions, namespaces: Option<V
         Ok(SelectResult::Listed)
     }
 }
+
+/// Open a picker over the contexts, then over the namespaces of the selected contexts, with
+/// multiple selection enabled in the options. A selected namespace is paired with each of the
+/// selected contexts in which it exists. Nothing is returned when either picker is cancelled.
+pub fn select_contexts_and_namespaces(
+    settings: &Settings,
+    skim_options: &SkimOptions,
+    installed: &mut Installed,
+) -> Result<Vec<(String, String)>> {
+    // The output may be captured, e.g. `$(kubie export -i)`, the picker only needs the terminal.
+    if !io::stdin().is_terminal() {
+        bail!("The interactive mode requires a terminal");
+    }
+
+    let state = State::load()?;
+    let context_names = sorted_context_names(settings, &state, installed);
+    if context_names.is_empty() {
+        bail!("No contexts found");
+    }
+    let context_names = pick_many(settings, skim_options, context_items(settings, &state, context_names))?;
+    if context_names.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut context_namespaces = Vec::with_capacity(context_names.len());
+    for context_name in context_names {
+        let kubeconfig = installed.make_kubeconfig_for_context(&context_name, None::<String>)?;
+        let namespaces: BTreeSet<_> = kubectl::get_namespaces(&kubeconfig)?.into_iter().collect();
+        context_namespaces.push((context_name, namespaces));
+    }
+
+    // Namespaces are sorted over all the selected contexts, a namespace is pinned when it is
+    // pinned in any of them.
+    let mut namespaces: Vec<_> = context_namespaces
+        .iter()
+        .flat_map(|(_, namespaces)| namespaces)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .cloned()
+        .collect();
+    let is_pinned = |name: &str| {
+        context_namespaces
+            .iter()
+            .any(|(context_name, _)| state.is_namespace_pinned(context_name, name))
+    };
+    if settings.picker.sort == PickerSort::Frecency {
+        sort_by_score(&mut namespaces, |name| {
+            context_namespaces
+                .iter()
+                .map(|(context_name, _)| state.namespace_frecency(context_name, name))
+                .sum()
+        });
+    }
+    namespaces.sort_by_key(|name| !is_pinned(name));
+    if namespaces.is_empty() {
+        bail!("No namespaces found");
+    }
+
+    let items = namespaces
+        .into_iter()
+        .map(|name| PickerItem {
+            display: pinned_display(is_pinned(&name), name.clone()),
+            value: name,
+        })
+        .collect();
+    let namespace_names = pick_many(settings, skim_options, items)?;
+
+    let mut selected = vec![];
+    for (context_name, namespaces) in &context_namespaces {
+        for namespace_name in &namespace_names {
+            if namespaces.contains(namespace_name) {
+                selected.push((context_name.clone(), namespace_name.clone()));
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Print rows aligned in columns, in the style of kubectl. Cells beyond the headers are not printed.
+pub fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("   ");
+        println!("{}", line.trim_end());
+    };
+
+    print_row(headers.to_vec());
+    for row in &rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
+
+/// Machine-readable formats of the `--output` flag.
+#[derive(Debug, Clone, Copy)]
+pub enum SerializeFormat {
+    Json,
+    Yaml,
+}
+
+/// Print a value in one of the machine-readable output formats.
+pub fn print_serialized<T: Serialize + ?Sized>(format: SerializeFormat, value: &T) -> Result<()> {
+    match format {
+        SerializeFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        SerializeFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+    }
+    Ok(())
+}
@@ -1,13 +1,16 @@
+use std::path::Path;
+
 use anyhow::{anyhow, Context, Result};
 use skim::SkimOptions;
 
-use crate::cmd::{select_or_list_namespace, SelectResult};
-use crate::kubeconfig;
+use crate::cmd::{history, select_or_list_namespace, SelectResult};
+use crate::ioutil;
+use crate::kubeconfig::{self, KubeConfig};
 use crate::kubectl;
 use crate::session::Session;
 use crate::settings::{Settings, ValidateNamespacesBehavior};
-use crate::shell::spawn_shell;
-use crate::state::State;
+use crate::shell::{self, spawn_shell};
+use crate::state::{paths, State};
 use crate::vars;
 
 pub fn namespace(
@@ -17,12 +20,35 @@ pub fn namespace(
     recursive: bool,
     unset: bool,
 ) -> Result<()> {
-    vars::ensure_kubie_active()?;
+    // In global mode, the global kubeconfig is switched instead of the kubie shell's.
+    let (config_path, session_path) = if settings.behavior.is_global() {
+        (paths::global_kubeconfig().to_path_buf(), Some(paths::global_session()))
+    } else {
+        vars::ensure_kubie_active()?;
+        (kubeconfig::get_kubeconfig_path()?, None)
+    };
 
-    let mut session = Session::load().context("Could not load session file")?;
+    let config: KubeConfig = ioutil::read_yaml(&config_path)
+        .with_context(|| format!("Could not read {}, use `kubie ctx` first", config_path.display()))?;
+    let mut session = match session_path {
+        Some(path) => Session::load_from(path),
+        None => Session::load(),
+    }
+    .context("Could not load session file")?;
+    let target = Target {
+        config_path: &config_path,
+        session_path,
+        config,
+    };
 
     if namespace_name.is_none() && unset {
-        return enter_namespace(settings, &mut session, recursive, None);
+        return enter_namespace(settings, &target, &mut session, recursive, None);
+    }
+
+    if let Some(index) = namespace_name.as_deref().and_then(history::parse_ref) {
+        let context_name = &target.config.contexts[0].name;
+        let namespace_name = history::get_namespace(&State::load()?, context_name, index)?;
+        return enter_namespace(settings, &target, &mut session, recursive, namespace_name);
     }
 
     let namespace_name = match namespace_name {
@@ -35,14 +61,14 @@ pub fn namespace(
         Some(s) => match settings.behavior.validate_namespaces {
             ValidateNamespacesBehavior::False => Some(s),
             ValidateNamespacesBehavior::True => {
-                let namespaces = kubectl::get_namespaces(None)?;
+                let namespaces = kubectl::get_namespaces(&target.config)?;
                 if !namespaces.contains(&s) {
                     return Err(anyhow!("'{}' is not a valid namespace for the context", s));
                 }
                 Some(s)
             }
             ValidateNamespacesBehavior::Partial => {
-                let namespaces = kubectl::get_namespaces(None)?;
+                let namespaces = kubectl::get_namespaces(&target.config)?;
                 if namespaces.contains(&s) {
                     Some(s)
                 } else {
@@ -51,7 +77,12 @@ pub fn namespace(
                     match ns_partial_matches.len() {
                         0 => return Err(anyhow!("'{}' is not a valid namespace for the context", s)),
                         1 => Some(ns_partial_matches[0].clone()),
-                        _ => match select_or_list_namespace(skim_options, Some(ns_partial_matches))? {
+                        _ => match select_or_list_namespace(
+                            settings,
+                            skim_options,
+                            &target.config.contexts[0].name,
+                            ns_partial_matches,
+                        )? {
                             SelectResult::Selected(s) => Some(s),
                             _ => return Ok(()),
                         },
@@ -59,45 +90,66 @@ pub fn namespace(
                 }
             }
         },
-        None => match select_or_list_namespace(skim_options, None)? {
+        None => match select_or_list_namespace(
+            settings,
+            skim_options,
+            &target.config.contexts[0].name,
+            kubectl::get_namespaces(&target.config)?,
+        )? {
             SelectResult::Selected(s) => Some(s),
             _ => return Ok(()),
         },
     };
 
-    enter_namespace(settings, &mut session, recursive, namespace_name)
+    enter_namespace(settings, &target, &mut session, recursive, namespace_name)
+}
+
+/// Kubeconfig switched in place by `kubie ns`.
+struct Target<'a> {
+    config_path: &'a Path,
+    /// Session file, or the session of the kubie shell when `None`.
+    session_path: Option<&'a Path>,
+    config: KubeConfig,
 }
 
 fn enter_namespace(
     settings: &Settings,
+    target: &Target,
     session: &mut Session,
     recursive: bool,
     namespace_name: Option<String>,
 ) -> Result<()> {
-    let mut config = kubeconfig::get_current_config()?;
+    let old_config = &target.config;
+    let mut config = old_config.clone();
     config.contexts[0].context.namespace = namespace_name.clone();
 
     let context_name = &config.contexts[0].name;
 
-    // Update the state, set the last namespace used for the context.
+    // Update the state once the namespace is entered, set the last namespace used for the context.
     // We take out a file lock here to avoid concurrent kubie processes
     // corrupting the state file
-    State::modify(|state| {
-        state
-            .namespace_history
-            .insert(context_name.into(), namespace_name.clone());
-        Ok(())
-    })?;
+    let update_state = || {
+        State::modify(|state| {
+            state
+                .namespace_history
+                .insert(context_name.into(), namespace_name.clone());
+            state.record_history(context_name, namespace_name.as_deref());
+            Ok(())
+        })
+    };
 
     // Update the history, add the context and namespace to it.
-    session.add_history_entry(context_name, namespace_name);
+    session.add_history_entry(context_name, namespace_name.clone());
 
     if recursive {
+        update_state()?;
         spawn_shell(settings, config, session)?;
     } else {
-        let config_file = kubeconfig::get_kubeconfig_path()?;
-        config.write_to_file(config_file.as_path())?;
-        session.save(None)?;
+        config.write_to_file(target.config_path)?;
+        session.save(target.session_path)?;
+        update_state()?;
+        shell::sync_env_file(settings, session, old_config, &config)?;
+        shell::hooks::run_switch_hooks(settings, old_config, &config);
     }
 
     Ok(())
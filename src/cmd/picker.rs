@@ -0,0 +1,91 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::PickerItem;
+
+/// Command of the fzf backend, configured by `FZF_DEFAULT_OPTS`.
+pub(super) fn fzf_command(multi: bool, query: Option<&str>) -> Command {
+    let mut cmd = Command::new("fzf");
+    if multi {
+        cmd.arg("--multi");
+    }
+    if let Some(query) = query {
+        cmd.arg("--query").arg(query);
+    }
+    cmd
+}
+
+/// Command of the command backend, `picker.command`. Multiple selection cannot be passed to an
+/// arbitrary command, the `{query}` placeholder gives it the initial query.
+pub(super) fn custom_command(command: &str, query: Option<&str>) -> Result<Command> {
+    let args = shlex::split(command)
+        .filter(|args| !args.is_empty())
+        .ok_or_else(|| anyhow!("Invalid picker command: {}", command))?;
+    let mut cmd = Command::new(&args[0]);
+    cmd.args(
+        args[1..]
+            .iter()
+            .map(|arg| arg.replace("{query}", query.unwrap_or_default())),
+    );
+    Ok(cmd)
+}
+
+/// Run an external picker, writing the text of the items to its stdin and reading the selected
+/// lines back from its stdout. The picker draws on the terminal itself, as fzf does. Nothing is
+/// selected when the picker exits with 1 or 130, which is how fzf reports that nothing matched or
+/// that it was interrupted.
+pub(super) fn run(mut cmd: Command, items: Vec<PickerItem>) -> Result<Vec<String>> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run the picker {}", cmd.get_program().to_string_lossy()))?;
+
+    let input: String = items.iter().map(|item| format!("{}\n", item.display)).collect();
+    if let Some(mut stdin) = child.stdin.take() {
+        // The picker may exit without reading all of its input.
+        let _ = stdin.write_all(input.as_bytes());
+    }
+
+    let output = child.wait_with_output()?;
+    match output.status.code() {
+        Some(0) => {}
+        Some(1 | 130) => return Ok(vec![]),
+        _ => bail!(
+            "The picker {} failed: {}",
+            cmd.get_program().to_string_lossy(),
+            output.status
+        ),
+    }
+    Ok(selected_values(&items, &String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Values of the items whose text was printed by the picker, one per line.
+fn selected_values(items: &[PickerItem], output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| items.iter().find(|item| item.display == line))
+        .map(|item| item.value.clone())
+        .collect()
+}
+
+#[test]
+fn test_selected_values() {
+    let items = vec![
+        PickerItem {
+            value: "prod".into(),
+            display: "★ prod  [env=prod]".into(),
+        },
+        PickerItem {
+            value: "dev".into(),
+            display: "dev".into(),
+        },
+    ];
+    assert_eq!(
+        selected_values(&items, "dev\n★ prod  [env=prod]\n"),
+        vec!["dev", "prod"]
+    );
+    assert_eq!(selected_values(&items, "unknown\n"), Vec::<String>::new());
+}
@@ -0,0 +1,105 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::kubeconfig;
+use crate::settings::Settings;
+use crate::state::State;
+
+/// Split `<context>/<namespace>` into the context and namespace names. Context names can contain
+/// slashes, such as the ARNs of EKS clusters, so the name is only split when it does not match a
+/// context as a whole.
+fn split_name(name: &str, is_context: impl Fn(&str) -> bool) -> Option<(&str, Option<&str>)> {
+    if is_context(name) {
+        return Some((name, None));
+    }
+    let (context_name, namespace_name) = name.rsplit_once('/')?;
+    is_context(context_name).then_some((context_name, Some(namespace_name)))
+}
+
+pub fn pin(settings: &Settings, name: Option<String>) -> Result<()> {
+    let name = match name {
+        Some(name) => name,
+        None => {
+            let state = State::load()?;
+            for context_name in &state.pinned_contexts {
+                println!("{context_name}");
+            }
+            for (context_name, namespaces) in &state.pinned_namespaces {
+                for namespace_name in namespaces {
+                    println!("{context_name}/{namespace_name}");
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let is_context = |name: &str| installed.find_context_by_name(name).is_some();
+    let (context_name, namespace_name) =
+        split_name(&name, is_context).ok_or_else(|| anyhow!("No context matching {}", name))?;
+    let context_name = context_name.to_string();
+    let namespace_name = namespace_name.map(String::from);
+
+    State::modify(move |state| {
+        match namespace_name {
+            Some(namespace_name) => {
+                state
+                    .pinned_namespaces
+                    .entry(context_name)
+                    .or_default()
+                    .insert(namespace_name);
+            }
+            None => {
+                state.pinned_contexts.insert(context_name);
+            }
+        }
+        Ok(())
+    })
+}
+
+pub fn unpin(name: String) -> Result<()> {
+    State::modify(move |state| {
+        let is_context =
+            |name: &str| state.pinned_contexts.contains(name) || state.pinned_namespaces.contains_key(name);
+        let (context_name, namespace_name) = match split_name(&name, is_context) {
+            Some((context_name, namespace_name)) => (context_name.to_string(), namespace_name.map(String::from)),
+            None => bail!("{} is not pinned", name),
+        };
+
+        let unpinned = match namespace_name {
+            Some(namespace_name) => {
+                let namespaces = state.pinned_namespaces.entry(context_name.clone()).or_default();
+                let unpinned = namespaces.remove(&namespace_name);
+                if namespaces.is_empty() {
+                    state.pinned_namespaces.remove(&context_name);
+                }
+                unpinned
+            }
+            None => state.pinned_contexts.remove(&context_name),
+        };
+        if !unpinned {
+            bail!("{} is not pinned", name);
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn test_split_name() {
+    let contexts = ["prod", "arn:aws:eks:eu-west-1:1234:cluster/prod"];
+    let is_context = |name: &str| contexts.contains(&name);
+
+    assert_eq!(split_name("prod", is_context), Some(("prod", None)));
+    assert_eq!(
+        split_name("prod/kube-system", is_context),
+        Some(("prod", Some("kube-system")))
+    );
+    assert_eq!(
+        split_name("arn:aws:eks:eu-west-1:1234:cluster/prod", is_context),
+        Some(("arn:aws:eks:eu-west-1:1234:cluster/prod", None))
+    );
+    assert_eq!(
+        split_name("arn:aws:eks:eu-west-1:1234:cluster/prod/default", is_context),
+        Some(("arn:aws:eks:eu-west-1:1234:cluster/prod", Some("default")))
+    );
+    assert_eq!(split_name("dev/default", is_context), None);
+}
@@ -0,0 +1,315 @@
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+
+use crate::cmd::lint::check_context_references;
+use crate::kubeconfig::{self, Sourced};
+use crate::settings::{expanduser, Settings};
+
+/// Pick the entry to modify among all the entries with the same name. When the name exists in more
+/// than one file, the user must tell us which file to modify.
+/// The file is compared with the sources once both are canonicalized, so that relative paths and
+/// paths starting with `~` match.
+fn pick_source<T>(kind: &str, name: &str, entries: Vec<&Sourced<T>>, file: Option<&str>) -> Result<Rc<PathBuf>> {
+    let entries: Vec<_> = match file {
+        Some(file) => {
+            let file = fs::canonicalize(expanduser(file)).with_context(|| format!("Could not find file {}", file))?;
+            entries
+                .into_iter()
+                .filter(|s| fs::canonicalize(s.source.as_path()).is_ok_and(|source| source == file))
+                .collect()
+        }
+        None => entries,
+    };
+
+    match entries.as_slice() {
+        [] => bail!("Could not find {} {}", kind, name),
+        [entry] => Ok(entry.source.clone()),
+        _ => {
+            let files: Vec<_> = entries.iter().map(|s| s.source.display().to_string()).collect();
+            bail!(
+                "The {} {} is defined in multiple files, use --kubeconfig to pick one of: {}",
+                kind,
+                name,
+                files.join(", ")
+            )
+        }
+    }
+}
+
+fn set_mapping_value(mapping: &mut Mapping, key: &str, value: Option<Value>) {
+    if let Some(value) = value {
+        mapping.insert(key.into(), value);
+    }
+}
+
+pub fn set_context(
+    settings: &Settings,
+    context_name: String,
+    file: Option<String>,
+    namespace_name: Option<String>,
+    unset_namespace: bool,
+    cluster_name: Option<String>,
+    user_name: Option<String>,
+) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let contexts = installed.find_contexts_by_name(&context_name);
+    let source = pick_source("context", &context_name, contexts.clone(), file.as_deref())?;
+    let context_src = contexts
+        .into_iter()
+        .find(|s| s.source == source)
+        .ok_or_else(|| anyhow!("Could not find context {}", context_name))?;
+
+    let mut context = context_src.item.context.clone();
+    if let Some(cluster_name) = cluster_name {
+        context.cluster = cluster_name;
+    }
+    if let Some(user_name) = user_name {
+        context.user = user_name;
+    }
+    if unset_namespace {
+        context.namespace = None;
+    } else if namespace_name.is_some() {
+        context.namespace = namespace_name;
+    }
+
+    let issues = check_context_references(&installed, &context, &context_src.source);
+    if !issues.is_empty() {
+        bail!("Context '{}' would reference {}", context_name, issues.join(" and "));
+    }
+
+    kubeconfig::modify_kubeconfig(&context_src.source, |kubeconfig| {
+        let named = kubeconfig
+            .contexts
+            .iter_mut()
+            .find(|c| c.name == context_name)
+            .ok_or_else(|| anyhow!("Context {} disappeared from its file", context_name))?;
+        named.context = context;
+        Ok(())
+    })?;
+
+    println!("Updated context {} in {}.", context_name, context_src.source.display());
+    Ok(())
+}
+
+pub fn set_cluster(
+    settings: &Settings,
+    cluster_name: String,
+    file: Option<String>,
+    server: Option<String>,
+    certificate_authority: Option<String>,
+    insecure_skip_tls_verify: Option<bool>,
+) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let source = pick_source(
+        "cluster",
+        &cluster_name,
+        installed.find_clusters_by_name(&cluster_name),
+        file.as_deref(),
+    )?;
+
+    kubeconfig::modify_kubeconfig(&source, |kubeconfig| {
+        let named = kubeconfig
+            .clusters
+            .iter_mut()
+            .find(|c| c.name == cluster_name)
+            .ok_or_else(|| anyhow!("Cluster {} disappeared from its file", cluster_name))?;
+        set_mapping_value(&mut named.cluster, "server", server.map(Value::from));
+        set_mapping_value(
+            &mut named.cluster,
+            "certificate-authority",
+            certificate_authority.map(Value::from),
+        );
+        set_mapping_value(
+            &mut named.cluster,
+            "insecure-skip-tls-verify",
+            insecure_skip_tls_verify.map(Value::from),
+        );
+        Ok(())
+    })?;
+
+    if installed.find_contexts_by_cluster(&cluster_name, &source).is_empty() {
+        eprintln!(
+            "Warning: cluster '{}' has no context referencing it in file {}",
+            cluster_name,
+            source.display()
+        );
+    }
+
+    println!("Updated cluster {} in {}.", cluster_name, source.display());
+    Ok(())
+}
+
+pub fn set_user(
+    settings: &Settings,
+    user_name: String,
+    file: Option<String>,
+    token: Option<String>,
+    client_certificate: Option<String>,
+    client_key: Option<String>,
+) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let source = pick_source(
+        "user",
+        &user_name,
+        installed.find_users_by_name(&user_name),
+        file.as_deref(),
+    )?;
+
+    kubeconfig::modify_kubeconfig(&source, |kubeconfig| {
+        let named = kubeconfig
+            .users
+            .iter_mut()
+            .find(|u| u.name == user_name)
+            .ok_or_else(|| anyhow!("User {} disappeared from its file", user_name))?;
+        set_mapping_value(&mut named.user, "token", token.map(Value::from));
+        set_mapping_value(
+            &mut named.user,
+            "client-certificate",
+            client_certificate.map(Value::from),
+        );
+        set_mapping_value(&mut named.user, "client-key", client_key.map(Value::from));
+        Ok(())
+    })?;
+
+    if installed.find_contexts_by_user(&user_name, &source).is_empty() {
+        eprintln!(
+            "Warning: user '{}' has no context referencing it in file {}",
+            user_name,
+            source.display()
+        );
+    }
+
+    println!("Updated user {} in {}.", user_name, source.display());
+    Ok(())
+}
+
+#[test]
+fn test_set_preserves_unknown_fields() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(
+        &path,
+        r#"
+apiVersion: v1
+kind: Config
+preferences: {colors: true}
+clusters:
+  - name: eks
+    cluster:
+      server: https://old.example.com
+      extensions:
+        - name: cluster-ext
+          extension: {audience: eks}
+users:
+  - name: admin
+    user: {token: abc}
+contexts:
+  - name: prod
+    context:
+      cluster: eks
+      user: admin
+      extensions:
+        - name: context-ext
+          extension: {foo: bar}
+current-context: prod
+"#,
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+    let settings: Settings = serde_yaml::from_str(&format!(
+        "configs: {{include: ['{}/*.yaml'], exclude: []}}",
+        dir.path().display()
+    ))
+    .unwrap();
+    set_context(
+        &settings,
+        "prod".into(),
+        None,
+        Some("kube-system".into()),
+        false,
+        None,
+        None,
+    )
+    .unwrap();
+    // A path which is not literally the source of the cluster must match it too.
+    let file = format!(
+        "{}/../{}/config.yaml",
+        dir.path().display(),
+        dir.path().file_name().unwrap().to_string_lossy()
+    );
+    set_cluster(
+        &settings,
+        "eks".into(),
+        Some(file),
+        Some("https://new.example.com".into()),
+        None,
+        None,
+    )
+    .unwrap();
+    set_user(&settings, "admin".into(), None, Some("def".into()), None, None).unwrap();
+
+    let config: Value = serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(config["preferences"]["colors"], Value::Bool(true));
+    assert_eq!(config["contexts"][0]["context"]["namespace"], "kube-system");
+    assert_eq!(config["contexts"][0]["context"]["extensions"][0]["name"], "context-ext");
+    assert_eq!(config["clusters"][0]["cluster"]["server"], "https://new.example.com");
+    assert_eq!(config["clusters"][0]["cluster"]["extensions"][0]["name"], "cluster-ext");
+    assert_eq!(config["users"][0]["user"]["token"], "def");
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_set_context_picks_file() {
+    use std::path::Path;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = r#"
+clusters:
+  - name: eks
+    cluster: {server: https://example.com}
+users:
+  - name: admin
+    user: {token: abc}
+contexts:
+  - name: prod
+    context: {cluster: eks, user: admin}
+"#;
+    let first = dir.path().join("first.yaml");
+    let second = dir.path().join("second.yaml");
+    fs::write(&first, config).unwrap();
+    fs::write(&second, config).unwrap();
+
+    let settings: Settings = serde_yaml::from_str(&format!(
+        "configs: {{include: ['{}/*.yaml'], exclude: []}}",
+        dir.path().display()
+    ))
+    .unwrap();
+    let set_namespace = |file: Option<&Path>| {
+        set_context(
+            &settings,
+            "prod".into(),
+            file.map(|f| f.display().to_string()),
+            Some("kube-system".into()),
+            false,
+            None,
+            None,
+        )
+    };
+    assert!(set_namespace(None).is_err());
+    set_namespace(Some(&second)).unwrap();
+
+    let namespace = |path: &Path| {
+        let config: Value = serde_yaml::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        config["contexts"][0]["context"]["namespace"].clone()
+    };
+    assert_eq!(namespace(&first), Value::Null);
+    assert_eq!(namespace(&second), "kube-system");
+}
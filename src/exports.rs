@@ -0,0 +1,204 @@
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+
+use crate::kubeconfig::KubeConfig;
+use crate::shell;
+use crate::state::{now, paths, ExportEntry, State};
+
+/// Age after which a file of the exports directory which is not tracked is removed.
+const UNTRACKED_GRACE: Duration = Duration::from_secs(60);
+
+/// Prefix and suffix of the names of the exported files.
+const EXPORT_PREFIX: &str = "kubie-config";
+const EXPORT_SUFFIX: &str = ".yaml";
+/// Extension of the session file written next to an exported file by `kubie env`.
+const SESSION_EXTENSION: &str = "session.json";
+
+/// Whether the file was written by `create`, other files of the exports directory are left alone.
+fn is_export_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(EXPORT_PREFIX) && name.ends_with(EXPORT_SUFFIX))
+}
+
+/// Session file of an exported file, removed along with it.
+pub fn session_path(path: &Path) -> PathBuf {
+    path.with_extension(SESSION_EXTENSION)
+}
+
+/// Exported file of a session file written next to it, `None` for other files.
+fn session_export_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(SESSION_EXTENSION)?.strip_suffix('.')?;
+    let export_path = path.with_file_name(format!("{stem}{EXPORT_SUFFIX}"));
+    is_export_file(&export_path).then_some(export_path)
+}
+
+fn is_running(pid: u32) -> bool {
+    // Signal 0 only checks whether the process exists. A process owned by another user exists too.
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+impl ExportEntry {
+    pub fn is_expired(&self) -> bool {
+        self.expires <= now()
+    }
+
+    pub fn is_in_use(&self) -> bool {
+        self.owner.is_some_and(is_running)
+    }
+
+    pub fn expires_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.expires)
+    }
+}
+
+/// Write the kubeconfig of a single context in the exports directory. The file is removed by the
+/// garbage collection once the TTL has expired and the process which invoked kubie has exited.
+pub fn create(config: &KubeConfig, ttl: Duration) -> Result<PathBuf> {
+    let dir = paths::exports_dir();
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("Could not create exports dir: {}", dir.display()))?;
+
+    let temp_config_file = tempfile::Builder::new()
+        .prefix(EXPORT_PREFIX)
+        .suffix(EXPORT_SUFFIX)
+        .tempfile_in(dir)?;
+    config.write_to_file(temp_config_file.path())?;
+    let (_, path) = temp_config_file.keep()?;
+
+    let entry = ExportEntry {
+        context: config.current_context.clone().unwrap_or_default(),
+        namespace: config.contexts[0]
+            .context
+            .namespace
+            .clone()
+            .unwrap_or_else(|| "default".into()),
+        expires: now() + ttl.as_secs(),
+        owner: shell::caller_pid(),
+    };
+    let key = path.display().to_string();
+    State::modify(move |state| {
+        state.exports.insert(key, entry);
+        Ok(())
+    })?;
+
+    Ok(path)
+}
+
+/// Remove an exported file and its session file, and stop tracking them.
+pub fn remove(path: &Path) -> Result<()> {
+    remove_export_files(path)?;
+    let key = path.display().to_string();
+    State::modify(move |state| {
+        state.exports.remove(&key);
+        Ok(())
+    })
+}
+
+fn remove_export_files(path: &Path) -> Result<()> {
+    remove_file(path)?;
+    remove_file(&session_path(path))
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("Could not remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Remove the exported files whose TTL has expired and which are not in use anymore, as well as
+/// the exported files which are not tracked, left by a kubie process which failed after writing
+/// them. Returns the removed files.
+pub fn gc() -> Result<Vec<PathBuf>> {
+    let mut removed = vec![];
+    let mut removed_ref = AssertUnwindSafe(&mut removed);
+    State::modify(move |state| {
+        let mut kept = state.exports.clone();
+        for (key, entry) in &state.exports {
+            let path = Path::new(key);
+            if !path.exists() {
+                kept.remove(key);
+            } else if entry.is_expired() && !entry.is_in_use() {
+                remove_export_files(path)?;
+                kept.remove(key);
+                removed_ref.push(path.to_path_buf());
+            }
+        }
+        state.exports = kept;
+
+        // A file is written before it is tracked, leave the recent files to the kubie process
+        // writing them.
+        if let Ok(dir) = fs::read_dir(paths::exports_dir()) {
+            for dir_entry in dir.flatten() {
+                let path = dir_entry.path();
+                let age = dir_entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.elapsed().ok())
+                    .unwrap_or_default();
+                // Session files are tracked through the exported file they are written next to.
+                let export_path = match session_export_path(&path) {
+                    Some(export_path) => export_path,
+                    None if is_export_file(&path) => path.clone(),
+                    None => continue,
+                };
+                if !state.exports.contains_key(&export_path.display().to_string()) && age > UNTRACKED_GRACE {
+                    remove_file(&path)?;
+                    removed_ref.push(path);
+                }
+            }
+        }
+        Ok(())
+    })?;
+    Ok(removed)
+}
+
+/// Run the garbage collection if any exported file has expired. This runs on most kubie invocations,
+/// the state is only locked for writing when there is work to do. Failing to remove expired files
+/// does not prevent the command from running.
+pub fn gc_if_needed() {
+    if !paths::exports_dir().exists() {
+        return;
+    }
+    let result = State::load().and_then(|state| {
+        if state.exports.values().any(|e| e.is_expired() && !e.is_in_use()) {
+            gc()?;
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        eprintln!("Warning: could not remove the expired exports: {err}");
+    }
+}
+
+#[test]
+fn test_is_export_file() {
+    assert!(is_export_file(Path::new("/data/exports/kubie-configAbC123.yaml")));
+    assert!(!is_export_file(Path::new("/data/exports/notes.txt")));
+    assert!(!is_export_file(Path::new("/data/exports/kubie-config.yaml.bak")));
+}
+
+#[test]
+fn test_session_path() {
+    let path = Path::new("/data/exports/kubie-configAbC123.yaml");
+    let session = session_path(path);
+    assert_eq!(session, Path::new("/data/exports/kubie-configAbC123.session.json"));
+    assert_eq!(session_export_path(&session).as_deref(), Some(path));
+    assert_eq!(session_export_path(path), None);
+    assert_eq!(session_export_path(Path::new("/data/exports/notes.session.json")), None);
+}
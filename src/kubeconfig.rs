@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::env;
-use std::fs::{self, File, Permissions};
-use std::io::BufWriter;
+use std::fs::{self, Permissions};
+use std::io::{BufWriter, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
@@ -9,9 +9,9 @@ use std::rc::Rc;
 use anyhow::{anyhow, bail, Context as _, Result};
 use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
-use wildmatch::WildMatch;
 
 use crate::ioutil;
+use crate::matcher::ContextQuery;
 use crate::settings::Settings;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -37,6 +37,41 @@ pub struct NamedUser {
     pub user: Mapping,
 }
 
+impl NamedCluster {
+    pub fn server(&self) -> Option<&str> {
+        self.cluster.get("server").and_then(Value::as_str)
+    }
+}
+
+impl NamedUser {
+    /// Describe how the user authenticates, based on which fields are present in its definition.
+    pub fn auth_type(&self) -> String {
+        let has = |key: &str| self.user.contains_key(key);
+        if let Some(exec) = self.user.get("exec") {
+            match exec.get("command").and_then(Value::as_str) {
+                Some(command) => {
+                    let command = command.rsplit('/').next().unwrap_or(command);
+                    format!("exec ({command})")
+                }
+                None => "exec".into(),
+            }
+        } else if let Some(provider) = self.user.get("auth-provider") {
+            match provider.get("name").and_then(Value::as_str) {
+                Some(name) => format!("auth-provider ({name})"),
+                None => "auth-provider".into(),
+            }
+        } else if has("token") || has("tokenFile") {
+            "token".into()
+        } else if has("client-certificate") || has("client-certificate-data") {
+            "client-certificate".into()
+        } else if has("username") || has("password") {
+            "basic".into()
+        } else {
+            "none".into()
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct NamedContext {
     pub name: String,
@@ -46,8 +81,11 @@ pub struct NamedContext {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Context {
     pub cluster: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
     pub user: String,
+    #[serde(flatten)]
+    pub others: HashMap<String, Value>,
 }
 
 #[derive(Clone, Debug)]
@@ -73,12 +111,26 @@ pub struct Installed {
 }
 
 impl KubeConfig {
+    /// Write the kubeconfig with 0600 permissions. The file is replaced atomically, so that kubectl
+    /// and the shells reading it concurrently never see a partial file.
     pub fn write_to_file(&self, path: &Path) -> anyhow::Result<()> {
-        let file = File::create(path).context("could not write file")?;
-        fs::set_permissions(path, Permissions::from_mode(0o600))?;
+        self.replace_file(path, Permissions::from_mode(0o600))
+            .context("could not write file")
+    }
 
-        let buffer = BufWriter::new(file);
-        serde_yaml::to_writer(buffer, self)?;
+    /// Write the kubeconfig to a temporary file of the same directory and rename it over the path.
+    fn replace_file(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let temp_file = tempfile::Builder::new().prefix(".kubie-config").tempfile_in(dir)?;
+        let mut writer = BufWriter::new(temp_file.as_file());
+        serde_yaml::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        fs::set_permissions(temp_file.path(), permissions)?;
+        temp_file.persist(path)?;
         Ok(())
     }
 }
@@ -102,6 +154,18 @@ impl Installed {
             .or_else(|| self.users.iter().find(|s| s.item.name == name))
     }
 
+    pub fn find_contexts_by_name(&self, name: &str) -> Vec<&Sourced<NamedContext>> {
+        self.contexts.iter().filter(|s| s.item.name == name).collect()
+    }
+
+    pub fn find_clusters_by_name(&self, name: &str) -> Vec<&Sourced<NamedCluster>> {
+        self.clusters.iter().filter(|s| s.item.name == name).collect()
+    }
+
+    pub fn find_users_by_name(&self, name: &str) -> Vec<&Sourced<NamedUser>> {
+        self.users.iter().filter(|s| s.item.name == name).collect()
+    }
+
     pub fn find_contexts_by_cluster(&self, name: &str, source: &Path) -> Vec<&Sourced<NamedContext>> {
         self.contexts
             .iter()
@@ -116,26 +180,13 @@ impl Installed {
             .collect()
     }
 
-    pub fn get_contexts_matching(&self, pattern: &str, allow_multiple_context_patterns: bool) -> Vec<&Sourced<NamedContext>> {
-        let mut result = vec![];
-
-        let patterns = if allow_multiple_context_patterns {
-            pattern.split_whitespace().collect()
-        } else {
-            vec![pattern]
-        };
-
-        for p in &patterns {
-            let matcher = WildMatch::new(p);
-            let m: Vec<_> = self.contexts.iter().filter(|s| matcher.matches(&s.item.name)).collect();
-            if patterns.len() > 1 && m.is_empty() {
-                println!("WARNING: No context matching {p}");
-            }
-            result.extend(m);
-        }
-        result.sort_by_key(|k| &k.item.name);
-        result.dedup_by_key(|k| &k.item.name);
-        result
+    /// Select the contexts matching the given query, see `ContextQuery` for the syntax.
+    pub fn get_contexts_matching(
+        &self,
+        pattern: &str,
+        allow_multiple_context_patterns: bool,
+    ) -> Result<Vec<&Sourced<NamedContext>>> {
+        Ok(ContextQuery::parse(pattern, allow_multiple_context_patterns)?.select(self))
     }
 
     pub fn delete_context(&mut self, name: &str) -> Result<()> {
@@ -181,7 +232,14 @@ impl Installed {
         let str = mapping.get(key).unwrap().as_str().expect("value should be a string");
         let path = Path::new(str);
         if !path.is_absolute() {
-            mapping.insert(key.into(), parent.join(path).to_str().expect("path should be a valid unicode string").into());
+            mapping.insert(
+                key.into(),
+                parent
+                    .join(path)
+                    .to_str()
+                    .expect("path should be a valid unicode string")
+                    .into(),
+            );
         }
     }
 
@@ -198,7 +256,10 @@ impl Installed {
             .ok_or_else(|| anyhow!("Could not find context {}", context_name))?;
 
         context_src.item.context.namespace = namespace_name.map(Into::into);
-        let kubeconfig_dir = context_src.source.parent().expect("kubeconfig path should have a parent dir");
+        let kubeconfig_dir = context_src
+            .source
+            .parent()
+            .expect("kubeconfig path should have a parent dir");
 
         let cluster_src = self
             .find_cluster_by_name(&context_src.item.context.cluster, &context_src.source)
@@ -306,6 +367,21 @@ pub fn get_kubeconfigs_contexts(kubeconfigs: &Vec<String>) -> Result<Installed>
     Ok(installed)
 }
 
+/// Read the kubeconfig at the given path, let the closure modify it and write it back. The file is
+/// replaced by renaming a temp file with the same permissions over it, so that it is never left half
+/// written. A symlink is followed and its target replaced.
+pub fn modify_kubeconfig<F: FnOnce(&mut KubeConfig) -> Result<()>>(path: &Path, func: F) -> Result<()> {
+    let path = fs::canonicalize(path).with_context(|| format!("Could not find kubeconfig {}", path.display()))?;
+    let mut kubeconfig: KubeConfig =
+        ioutil::read_yaml(&path).with_context(|| format!("Could not read kubeconfig {}", path.display()))?;
+    func(&mut kubeconfig)?;
+
+    fs::metadata(&path)
+        .map_err(Into::into)
+        .and_then(|metadata| kubeconfig.replace_file(&path, metadata.permissions()))
+        .with_context(|| format!("Could not rewrite kubeconfig {}", path.display()))
+}
+
 pub fn get_kubeconfig_path() -> Result<PathBuf> {
     let path = env::var_os("KUBIE_KUBECONFIG").context("KUBIE_CONFIG not found")?;
     Ok(PathBuf::from(path))
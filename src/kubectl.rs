@@ -4,7 +4,8 @@ use std::str;
 
 use anyhow::{anyhow, Context};
 
-use crate::kubeconfig::KubeConfig;
+use crate::kubeconfig::{self, KubeConfig};
+use crate::state::State;
 
 pub fn get_namespaces<'a>(kubeconfig: impl Into<Option<&'a KubeConfig>>) -> anyhow::Result<Vec<String>> {
     let mut cmd = Command::new("kubectl");
@@ -12,8 +13,10 @@ pub fn get_namespaces<'a>(kubeconfig: impl Into<Option<&'a KubeConfig>>) -> anyh
     cmd.arg("namespaces");
 
     let temp_config_file;
+    let context_name;
 
     if let Some(kubeconfig) = kubeconfig.into() {
+        context_name = kubeconfig.current_context.clone();
         temp_config_file = tempfile::Builder::new()
             .prefix("kubie-config")
             .suffix(".yaml")
@@ -21,6 +24,7 @@ pub fn get_namespaces<'a>(kubeconfig: impl Into<Option<&'a KubeConfig>>) -> anyh
         kubeconfig.write_to_file(temp_config_file.path())?;
         cmd.env("KUBECONFIG", temp_config_file.path());
     } else {
+        context_name = kubeconfig::get_current_config().ok().and_then(|c| c.current_context);
         cmd.env(
             "KUBECONFIG",
             env::var("KUBIE_KUBECONFIG").context("KUBIE_KUBECONFIG variable is not set")?,
@@ -40,5 +44,14 @@ pub fn get_namespaces<'a>(kubeconfig: impl Into<Option<&'a KubeConfig>>) -> anyh
         namespaces.push(line[..idx].to_string());
     }
 
+    if let Some(context_name) = context_name {
+        // The cache only speeds up completions, failing to update it must not fail the command.
+        let cached = namespaces.clone();
+        let _ = State::modify(move |state| {
+            state.namespace_cache.insert(context_name, cached);
+            Ok(())
+        });
+    }
+
     Ok(namespaces)
 }
@@ -1,14 +1,18 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 
-use cmd::meta::Kubie;
+use cmd::meta::{Kubie, KubieInfo, KubieInfoKind};
 use settings::Settings;
 use skim::prelude::SkimOptionsBuilder;
 
 mod cmd;
+mod exports;
 mod ioutil;
 mod kubeconfig;
 mod kubectl;
+mod matcher;
+mod selector;
 mod session;
 mod settings;
 mod shell;
@@ -16,9 +20,12 @@ mod state;
 mod vars;
 
 fn main() -> Result<()> {
+    // Answer the completion requests of the shells before anything is written to stdout.
+    CompleteEnv::with_factory(Kubie::command).complete();
+
     let mut settings = Settings::load()?;
 
-    let skim_options = {
+    let mut skim_options = {
         let mut options = SkimOptionsBuilder::default();
 
         options.no_multi(true);
@@ -50,20 +57,35 @@ fn main() -> Result<()> {
 
     let kubie = Kubie::parse();
 
+    // Expired exports are removed by any command, except the ones which run on every prompt.
+    let on_prompt = matches!(
+        kubie,
+        Kubie::PromptSegment { .. }
+            | Kubie::Info(KubieInfo {
+                kind: KubieInfoKind::Prompt
+            })
+    );
+    if !on_prompt {
+        exports::gc_if_needed();
+    }
+
     match kubie {
         Kubie::Context {
             namespace_name,
             context_name,
             kubeconfigs,
             recursive,
+            tags,
+            pinned,
         } => {
             cmd::context::context(
                 &settings,
-                &skim_options,
+                &mut skim_options,
                 context_name,
                 namespace_name,
                 kubeconfigs,
                 recursive,
+                cmd::context::ContextFilter { tags, pinned },
             )?;
         }
         Kubie::Namespace {
@@ -73,31 +95,115 @@ fn main() -> Result<()> {
         } => {
             cmd::namespace::namespace(&settings, &skim_options, namespace_name, recursive, unset)?;
         }
+        Kubie::Pin { name } => {
+            cmd::pin::pin(&settings, name)?;
+        }
+        Kubie::Unpin { name } => {
+            cmd::pin::unpin(name)?;
+        }
+        Kubie::History { output } => {
+            cmd::history::history(&settings, &skim_options, output)?;
+        }
         Kubie::Info(info) => {
-            cmd::info::info(info)?;
+            cmd::info::info(&settings, info)?;
+        }
+        Kubie::PromptSegment { format } => {
+            cmd::info::prompt_segment(&settings, format)?;
         }
         Kubie::Exec {
             context_name,
             namespace_name,
+            interactive,
             exit_early,
             context_headers_flag,
+            selectors,
             args,
         } => {
+            // Without a context and namespace, the positional arguments are all part of the command.
+            let (target, args) = if interactive {
+                skim_options.multi = true;
+                let args = context_name.into_iter().chain(namespace_name).chain(args).collect();
+                (None, args)
+            } else {
+                (context_name.zip(namespace_name), args)
+            };
             cmd::exec::exec(
                 &settings,
-                context_name,
-                namespace_name,
+                &skim_options,
+                target,
                 exit_early,
                 context_headers_flag,
+                selectors,
                 args,
             )?;
         }
+        Kubie::Env {
+            context_name,
+            namespace_name,
+            shell,
+            ttl,
+            unset,
+        } => {
+            cmd::env::env(&settings, context_name, namespace_name, shell, ttl, unset)?;
+        }
+        Kubie::List { kind, output, pattern } => {
+            cmd::ls::ls(&settings, kind, output, pattern)?;
+        }
+        Kubie::Gc => {
+            cmd::gc::gc()?;
+        }
+        Kubie::Doctor { kind } => {
+            cmd::doctor::doctor(&settings, kind)?;
+        }
         Kubie::Lint => {
             cmd::lint::lint(&settings)?;
         }
         Kubie::Edit { context_name } => {
             cmd::edit::edit_context(&settings, &skim_options, context_name)?;
         }
+        Kubie::SetContext {
+            context_name,
+            kubeconfig,
+            namespace_name,
+            unset_namespace,
+            cluster_name,
+            user_name,
+        } => {
+            cmd::set::set_context(
+                &settings,
+                context_name,
+                kubeconfig,
+                namespace_name,
+                unset_namespace,
+                cluster_name,
+                user_name,
+            )?;
+        }
+        Kubie::SetCluster {
+            cluster_name,
+            kubeconfig,
+            server,
+            certificate_authority,
+            insecure_skip_tls_verify,
+        } => {
+            cmd::set::set_cluster(
+                &settings,
+                cluster_name,
+                kubeconfig,
+                server,
+                certificate_authority,
+                insecure_skip_tls_verify,
+            )?;
+        }
+        Kubie::SetUser {
+            user_name,
+            kubeconfig,
+            token,
+            client_certificate,
+            client_key,
+        } => {
+            cmd::set::set_user(&settings, user_name, kubeconfig, token, client_certificate, client_key)?;
+        }
         Kubie::EditConfig => {
             cmd::edit::edit_config(&settings)?;
         }
@@ -111,11 +217,14 @@ fn main() -> Result<()> {
         Kubie::Export {
             context_name,
             namespace_name,
+            interactive,
+            ttl,
         } => {
-            cmd::export::export(&settings, context_name, namespace_name)?;
+            skim_options.multi = interactive;
+            cmd::export::export(&settings, &skim_options, context_name.zip(namespace_name), ttl)?;
         }
         Kubie::GenerateCompletion(cmd) => {
-            cmd::meta::generate_completion(cmd);
+            cmd::meta::generate_completion(cmd)?;
         }
     }
 
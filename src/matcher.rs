@@ -0,0 +1,282 @@
+use anyhow::{bail, Context as _, Result};
+use regex::Regex;
+use wildmatch::WildMatch;
+
+use crate::kubeconfig::{Installed, NamedContext, Sourced};
+
+/// Field of a context an atom is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Cluster,
+    User,
+    Server,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Glob(WildMatch),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob.matches(value),
+            Pattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Atom {
+    negated: bool,
+    field: Field,
+    pattern: Pattern,
+}
+
+impl Atom {
+    fn matches(&self, installed: &Installed, context_src: &Sourced<NamedContext>) -> bool {
+        let context = &context_src.item;
+        let found = match self.field {
+            Field::Name => self.pattern.matches(&context.name),
+            Field::Cluster => self.pattern.matches(&context.context.cluster),
+            Field::User => self.pattern.matches(&context.context.user),
+            Field::Server => installed
+                .find_cluster_by_name(&context.context.cluster, &context_src.source)
+                .and_then(|c| c.item.server())
+                .is_some_and(|server| self.pattern.matches(server_host(server)) || self.pattern.matches(server)),
+        };
+        found != self.negated
+    }
+}
+
+/// Extract the host from a cluster's server URL, e.g. `https://foo.example.com:6443/` gives `foo.example.com`.
+fn server_host(server: &str) -> &str {
+    let without_scheme = server.split_once("://").map(|(_, rest)| rest).unwrap_or(server);
+    let end = without_scheme.find(['/', ':']).unwrap_or(without_scheme.len());
+    &without_scheme[..end]
+}
+
+/// A term is a list of atoms which must all match.
+#[derive(Debug)]
+struct Term {
+    text: String,
+    atoms: Vec<Atom>,
+}
+
+impl Term {
+    fn parse(text: &str) -> Result<Term> {
+        let mut atoms = vec![];
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (negated, stripped) = match rest.strip_prefix('!') {
+                Some(stripped) => (true, stripped),
+                None => (false, rest),
+            };
+
+            let (field, stripped) = match stripped.split_once('=') {
+                Some(("name", value)) => (Field::Name, value),
+                Some(("cluster", value)) => (Field::Cluster, value),
+                Some(("user", value)) => (Field::User, value),
+                Some(("server", value)) => (Field::Server, value),
+                _ => (Field::Name, stripped),
+            };
+
+            // A regex may contain commas, so it always extends to the end of the term.
+            let (pattern, next) = if let Some(regex) = stripped.strip_prefix("re:") {
+                let regex = Regex::new(regex).with_context(|| format!("Invalid regex in pattern '{text}'"))?;
+                (Pattern::Regex(regex), "")
+            } else {
+                let (glob, next) = stripped.split_once(',').unwrap_or((stripped, ""));
+                if glob.is_empty() {
+                    bail!("Empty pattern in '{}'", text);
+                }
+                (Pattern::Glob(WildMatch::new(glob)), next)
+            };
+
+            atoms.push(Atom {
+                negated,
+                field,
+                pattern,
+            });
+            rest = next;
+        }
+
+        if atoms.is_empty() {
+            bail!("Empty context pattern");
+        }
+
+        Ok(Term {
+            text: text.to_string(),
+            atoms,
+        })
+    }
+
+    fn is_exclusion(&self) -> bool {
+        self.atoms.iter().all(|a| a.negated)
+    }
+
+    fn matches(&self, installed: &Installed, context_src: &Sourced<NamedContext>) -> bool {
+        self.atoms.iter().all(|a| a.matches(installed, context_src))
+    }
+}
+
+/// Query used to select contexts in `kubie exec`, `kubie export` and `kubie ls`.
+///
+/// A query is made of terms, each term is a comma separated list of atoms which must all match.
+/// An atom is a glob matched against the context name, optionally prefixed by a field such as
+/// `cluster=`, `user=` or `server=` (matched against the host of the cluster's server). The glob
+/// can be replaced by a regex with the `re:` prefix, in which case the regex extends to the end of
+/// the term. Atoms can be negated with `!`.
+///
+/// A context is selected if it matches any of the terms. Terms made only of negated atoms are
+/// exclusions: they remove contexts from the result instead of adding to it.
+#[derive(Debug)]
+pub struct ContextQuery {
+    terms: Vec<Term>,
+}
+
+impl ContextQuery {
+    /// Parse a query. Terms are separated by whitespace only when `allow_multiple` is set, otherwise
+    /// the whole query is a single term.
+    pub fn parse(query: &str, allow_multiple: bool) -> Result<ContextQuery> {
+        let terms = if allow_multiple {
+            query.split_whitespace().map(Term::parse).collect::<Result<Vec<_>>>()?
+        } else {
+            vec![Term::parse(query)?]
+        };
+        Ok(ContextQuery { terms })
+    }
+
+    pub fn select<'a>(&self, installed: &'a Installed) -> Vec<&'a Sourced<NamedContext>> {
+        let (exclusions, inclusions): (Vec<_>, Vec<_>) = self.terms.iter().partition(|t| t.is_exclusion());
+
+        let mut result: Vec<_> = if inclusions.is_empty() {
+            installed.contexts.iter().collect()
+        } else {
+            let mut result = vec![];
+            for term in &inclusions {
+                let m: Vec<_> = installed
+                    .contexts
+                    .iter()
+                    .filter(|s| term.matches(installed, s))
+                    .collect();
+                if self.terms.len() > 1 && m.is_empty() {
+                    println!("WARNING: No context matching {}", term.text);
+                }
+                result.extend(m);
+            }
+            result
+        };
+
+        result.retain(|s| exclusions.iter().all(|t| t.matches(installed, s)));
+        result.sort_by_key(|k| &k.item.name);
+        result.dedup_by_key(|k| &k.item.name);
+        result
+    }
+}
+
+#[cfg(test)]
+fn test_installed() -> Installed {
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use crate::kubeconfig::KubeConfig;
+
+    let kubeconfig: KubeConfig = serde_yaml::from_str(
+        r#"
+clusters:
+  - name: eks
+    cluster:
+      server: https://abc.eks.amazonaws.com:443
+  - name: gke
+    cluster:
+      server: https://10.0.0.1
+users:
+  - name: admin
+    user: {}
+  - name: dev
+    user: {}
+contexts:
+  - name: prod-eu
+    context: {cluster: eks, user: admin}
+  - name: prod-us
+    context: {cluster: gke, user: admin}
+  - name: prod-dev
+    context: {cluster: gke, user: dev}
+  - name: staging-dev
+    context: {cluster: eks, user: dev}
+"#,
+    )
+    .unwrap();
+
+    let source = Rc::new(PathBuf::from("/config"));
+    Installed {
+        clusters: kubeconfig
+            .clusters
+            .into_iter()
+            .map(|x| Sourced::new(&source, x))
+            .collect(),
+        users: kubeconfig.users.into_iter().map(|x| Sourced::new(&source, x)).collect(),
+        contexts: kubeconfig
+            .contexts
+            .into_iter()
+            .map(|x| Sourced::new(&source, x))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+fn select_names(query: &str) -> Vec<String> {
+    let installed = test_installed();
+    ContextQuery::parse(query, true)
+        .unwrap()
+        .select(&installed)
+        .iter()
+        .map(|s| s.item.name.clone())
+        .collect()
+}
+
+#[test]
+fn test_query_glob() {
+    assert_eq!(select_names("prod-*"), vec!["prod-dev", "prod-eu", "prod-us"]);
+}
+
+#[test]
+fn test_query_negation() {
+    assert_eq!(select_names("prod-* !*-dev"), vec!["prod-eu", "prod-us"]);
+    assert_eq!(select_names("!*-dev"), vec!["prod-eu", "prod-us"]);
+    assert_eq!(select_names("prod-*,!*-dev"), vec!["prod-eu", "prod-us"]);
+}
+
+#[test]
+fn test_query_regex() {
+    assert_eq!(select_names("re:^prod-(eu|us)$"), vec!["prod-eu", "prod-us"]);
+    assert_eq!(
+        select_names("user=dev,re:^[a-z]{4,7}-dev$"),
+        vec!["prod-dev", "staging-dev"]
+    );
+}
+
+#[test]
+fn test_query_fields() {
+    assert_eq!(
+        select_names("server=*.eks.amazonaws.com"),
+        vec!["prod-eu", "staging-dev"]
+    );
+    assert_eq!(select_names("cluster=gke,user=admin"), vec!["prod-us"]);
+}
+
+#[test]
+fn test_query_single_term() {
+    let installed = test_installed();
+    let query = ContextQuery::parse("prod-eu prod-us", false).unwrap();
+    assert!(query.select(&installed).is_empty());
+}
+
+#[test]
+fn test_server_host() {
+    assert_eq!(server_host("https://foo.example.com:6443/path"), "foo.example.com");
+    assert_eq!(server_host("foo.example.com"), "foo.example.com");
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+
+/// A single requirement of a label selector.
+#[derive(Debug, PartialEq, Eq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+    NotExists(String),
+}
+
+impl Requirement {
+    fn parse(text: &str) -> Result<Requirement> {
+        let text = text.trim();
+        if text.is_empty() {
+            bail!("Empty requirement in selector");
+        }
+
+        let requirement = if let Some((key, value)) = text.split_once("!=") {
+            Requirement::NotEquals(key.trim().into(), value.trim().into())
+        } else if let Some((key, value)) = text.split_once("==") {
+            Requirement::Equals(key.trim().into(), value.trim().into())
+        } else if let Some((key, value)) = text.split_once('=') {
+            Requirement::Equals(key.trim().into(), value.trim().into())
+        } else if let Some(key) = text.strip_prefix('!') {
+            Requirement::NotExists(key.trim().into())
+        } else {
+            Requirement::Exists(text.into())
+        };
+
+        match &requirement {
+            Requirement::Equals(key, _)
+            | Requirement::NotEquals(key, _)
+            | Requirement::Exists(key)
+            | Requirement::NotExists(key)
+                if key.is_empty() =>
+            {
+                bail!("Missing tag name in selector requirement '{}'", text)
+            }
+            _ => Ok(requirement),
+        }
+    }
+
+    fn matches(&self, tags: &BTreeMap<String, String>) -> bool {
+        match self {
+            Requirement::Equals(key, value) => tags.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => tags.get(key) != Some(value),
+            Requirement::Exists(key) => tags.contains_key(key),
+            Requirement::NotExists(key) => !tags.contains_key(key),
+        }
+    }
+}
+
+/// Label selector matched against context tags, using the same syntax as kubectl's `--selector`.
+///
+/// Requirements are separated by commas and must all match. Supported requirements are `key=value`,
+/// `key==value`, `key!=value`, `key` (the tag is set) and `!key` (the tag is not set).
+#[derive(Debug, Default)]
+pub struct Selector {
+    requirements: Vec<Requirement>,
+}
+
+impl Selector {
+    pub fn parse(text: &str) -> Result<Selector> {
+        let requirements = text.split(',').map(Requirement::parse).collect::<Result<_>>()?;
+        Ok(Selector { requirements })
+    }
+
+    /// Parse many selectors and combine all their requirements into a single selector.
+    pub fn parse_all<S: AsRef<str>>(texts: &[S]) -> Result<Selector> {
+        let mut selector = Selector::default();
+        for text in texts {
+            selector
+                .requirements
+                .extend(Selector::parse(text.as_ref())?.requirements);
+        }
+        Ok(selector)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    pub fn matches(&self, tags: &BTreeMap<String, String>) -> bool {
+        self.requirements.iter().all(|r| r.matches(tags))
+    }
+}
+
+#[cfg(test)]
+fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[test]
+fn test_selector_equals() {
+    let selector = Selector::parse("env=prod").unwrap();
+    assert!(selector.matches(&tags(&[("env", "prod")])));
+    assert!(!selector.matches(&tags(&[("env", "staging")])));
+    assert!(!selector.matches(&tags(&[])));
+}
+
+#[test]
+fn test_selector_combined() {
+    let selector = Selector::parse("env=prod, region!=us").unwrap();
+    assert!(selector.matches(&tags(&[("env", "prod"), ("region", "eu")])));
+    assert!(selector.matches(&tags(&[("env", "prod")])));
+    assert!(!selector.matches(&tags(&[("env", "prod"), ("region", "us")])));
+}
+
+#[test]
+fn test_selector_existence() {
+    let selector = Selector::parse("team,!deprecated").unwrap();
+    assert!(selector.matches(&tags(&[("team", "payments")])));
+    assert!(!selector.matches(&tags(&[("team", "payments"), ("deprecated", "yes")])));
+    assert!(!selector.matches(&tags(&[])));
+}
+
+#[test]
+fn test_selector_invalid() {
+    assert!(Selector::parse("env=prod,").is_err());
+    assert!(Selector::parse("=prod").is_err());
+}
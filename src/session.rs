@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+use std::env;
 use std::path::Path;
 
 use anyhow::{Context, Result};
@@ -9,10 +11,13 @@ use crate::vars;
 /// Session contains information which is scoped to a kubie shell.
 ///
 /// Currently it stores the history of contexts and namespaces entered to allow
-/// users to switch back to the previous context with `-`.
-#[derive(Debug, Default, Deserialize, Serialize)]
+/// users to switch back to the previous context with `-`, and the values the context
+/// variables had before the shell was spawned.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Session {
     history: Vec<HistoryEntry>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    original_env: BTreeMap<String, String>,
 }
 
 impl Session {
@@ -22,6 +27,10 @@ impl Session {
             Some(x) => x,
         };
 
+        Session::load_from(&session_path)
+    }
+
+    pub fn load_from(session_path: &Path) -> Result<Session> {
         if !session_path.exists() {
             return Ok(Default::default());
         }
@@ -45,6 +54,23 @@ impl Session {
         })
     }
 
+    /// Record the values of the given variables which are set in the current environment.
+    pub fn record_original_env<'a>(&mut self, names: impl IntoIterator<Item = &'a String>) {
+        self.original_env = names
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), env::var(name).ok()?)))
+            .collect();
+    }
+
+    /// Value of the variable before the shell was spawned, `None` if it was not set.
+    pub fn original_env(&self, name: &str) -> Option<&str> {
+        self.original_env.get(name).map(String::as_str)
+    }
+
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
     pub fn get_last_context(&self) -> Option<&HistoryEntry> {
         let current_context = self.history.last()?;
         self.history
@@ -68,7 +94,7 @@ impl Session {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct HistoryEntry {
     pub context: String,
     pub namespace: Option<String>,
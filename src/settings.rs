@@ -1,12 +1,16 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufReader, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use glob::glob;
 use lazy_static::lazy_static;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use wildmatch::WildMatch;
+
+use crate::vars;
 
 lazy_static! {
     static ref HOME_DIR: String = dirs::home_dir()
@@ -39,10 +43,45 @@ pub struct Fzf {
     pub color: Option<String>,
 }
 
+#[derive(Default, Debug, Deserialize)]
+pub struct Picker {
+    #[serde(default)]
+    pub sort: PickerSort,
+    #[serde(default)]
+    pub backend: PickerBackend,
+    /// Command run by the `command` backend, `{query}` is replaced by the initial query.
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+/// Program showing the pickers.
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PickerBackend {
+    /// The picker embedded in kubie, configured by the `fzf` settings.
+    #[default]
+    Skim,
+    /// The `fzf` binary, configured by `FZF_DEFAULT_OPTS`.
+    Fzf,
+    /// The command of `picker.command`.
+    Command,
+}
+
+/// Order of the entries in the context and namespace pickers.
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PickerSort {
+    /// Sort the entries by name.
+    #[default]
+    Alpha,
+    /// Show the most frequently and recently used entries first, see `kubie history`.
+    Frecency,
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub struct Settings {
     #[serde(default)]
-    pub shell: Option<String>,
+    pub shell: Option<ShellSetting>,
     #[serde(default)]
     pub default_editor: Option<String>,
     #[serde(default)]
@@ -55,6 +94,10 @@ pub struct Settings {
     pub hooks: Hooks,
     #[serde(default)]
     pub fzf: Fzf,
+    #[serde(default)]
+    pub picker: Picker,
+    #[serde(default)]
+    pub contexts: Vec<ContextSettings>,
 }
 
 impl Settings {
@@ -97,6 +140,57 @@ impl Settings {
 
         Ok(paths)
     }
+
+    /// Iterate over the context settings whose pattern matches the given context name, in the order
+    /// in which they appear in the settings file.
+    pub fn context_settings<'a>(&'a self, context_name: &'a str) -> impl Iterator<Item = &'a ContextSettings> + 'a {
+        self.contexts.iter().filter(move |c| c.matches(context_name))
+    }
+
+    /// Merge the metadata of every context settings entry matching the given context name. Entries
+    /// appearing later in the settings file take precedence.
+    pub fn context_metadata(&self, context_name: &str) -> ContextMetadata {
+        let mut metadata = ContextMetadata::default();
+        for entry in self.context_settings(context_name) {
+            if entry.description.is_some() {
+                metadata.description.clone_from(&entry.description);
+            }
+            if entry.owner.is_some() {
+                metadata.owner.clone_from(&entry.owner);
+            }
+            if entry.alias.is_some() {
+                metadata.alias.clone_from(&entry.alias);
+            }
+            metadata
+                .tags
+                .extend(entry.tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        metadata
+    }
+
+    /// Merge the environment variables of every context settings entry matching the given context
+    /// name, replacing `${ctx}` and `${ns}` in their values. Entries appearing later in the settings
+    /// file take precedence.
+    pub fn context_env(&self, context_name: &str, namespace_name: &str) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        for entry in self.context_settings(context_name) {
+            env.extend(entry.env.iter().map(|(k, v)| {
+                let value = v.replace("${ctx}", context_name).replace("${ns}", namespace_name);
+                (k.clone(), value)
+            }));
+        }
+        env
+    }
+
+    /// Resolve a context alias to the context's name. Only the context settings whose pattern is a
+    /// plain context name can be used as aliases, other names are returned as is.
+    pub fn resolve_context_alias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.contexts
+            .iter()
+            .find(|c| c.alias.as_deref() == Some(name) && !c.pattern.contains(['*', '?']))
+            .map(|c| c.pattern.as_str())
+            .unwrap_or(name)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -133,6 +227,69 @@ fn default_exclude_path() -> Vec<String> {
     vec![]
 }
 
+/// Shell spawned by kubie, either the name of a shell kubie knows or a custom shell.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ShellSetting {
+    Name(String),
+    Custom(CustomShell),
+}
+
+/// Shell which kubie does not know how to integrate with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomShell {
+    /// Command spawning the shell. `{rcfile}` is replaced by the path of the file rendered from `rc`.
+    pub command: String,
+    /// Template of the rc file. `{prompt}` is replaced by a command substitution printing kubie's
+    /// prompt and `{kubie}` by the path of kubie's binary.
+    #[serde(default)]
+    pub rc: String,
+}
+
+/// Settings applied to every context whose name matches the glob `pattern`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ContextSettings {
+    pub pattern: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub hooks: ContextHooks,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl ContextSettings {
+    pub fn matches(&self, context_name: &str) -> bool {
+        WildMatch::new(&self.pattern).matches(context_name)
+    }
+}
+
+/// Metadata of a context, resolved from all the context settings matching its name.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ContextMetadata {
+    pub description: Option<String>,
+    pub owner: Option<String>,
+    pub alias: Option<String>,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl ContextMetadata {
+    /// Format the tags as a comma separated list of `key=value` pairs.
+    pub fn tags_string(&self) -> String {
+        self.tags
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Prompt {
     #[serde(default = "def_bool_false")]
@@ -145,6 +302,136 @@ pub struct Prompt {
     pub fish_use_rprompt: bool,
     #[serde(default = "def_bool_false")]
     pub xonsh_use_right_prompt: bool,
+    #[serde(default = "def_bool_false")]
+    pub nu_use_right_prompt: bool,
+    #[serde(default = "def_bool_false")]
+    pub show_tags: bool,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub colors: BTreeMap<String, Style>,
+    #[serde(default)]
+    pub overrides: Vec<PromptOverride>,
+    #[serde(default)]
+    pub title: Title,
+}
+
+impl Prompt {
+    /// Template of the prompt, the default template reproduces kubie's historical prompt.
+    pub fn format(&self) -> String {
+        match &self.format {
+            Some(format) => format.clone(),
+            None if self.show_tags => "[{ctx}|{ns}{?|{tags}}{?|{depth}}]".into(),
+            None => "[{ctx}|{ns}{?|{depth}}]".into(),
+        }
+    }
+
+    /// Style of a placeholder of the prompt for the given context. Overrides matching the context
+    /// take precedence over the global colors, and later overrides take precedence over earlier ones.
+    pub fn style(&self, context_name: &str, placeholder: &str) -> Style {
+        let lookup = |colors: &BTreeMap<String, Style>| {
+            colors
+                .get(placeholder)
+                .or_else(|| Self::fallback_placeholder(placeholder).and_then(|p| colors.get(p)))
+                .cloned()
+        };
+
+        self.overrides
+            .iter()
+            .rev()
+            .filter(|o| WildMatch::new(&o.pattern).matches(context_name))
+            .find_map(|o| lookup(&o.colors))
+            .or_else(|| lookup(&self.colors))
+            .unwrap_or_else(|| Self::default_style(placeholder))
+    }
+
+    fn fallback_placeholder(placeholder: &str) -> Option<&'static str> {
+        match placeholder {
+            "alias" => Some("ctx"),
+            p if p.starts_with("tag:") => Some("tags"),
+            _ => None,
+        }
+    }
+
+    fn default_style(placeholder: &str) -> Style {
+        let color = match Self::fallback_placeholder(placeholder).unwrap_or(placeholder) {
+            "ctx" => "red",
+            "ns" => "green",
+            "depth" => "blue",
+            "tags" => "magenta",
+            _ => "none",
+        };
+        Style::try_from(color.to_string()).expect("default colors are valid")
+    }
+}
+
+/// Colors of the prompt for the contexts whose name matches the glob `pattern`.
+#[derive(Debug, Deserialize)]
+pub struct PromptOverride {
+    pub pattern: String,
+    #[serde(default)]
+    pub colors: BTreeMap<String, Style>,
+}
+
+/// Terminal style made of space separated words: named colors (`red`, `bright-blue`), 256 colors
+/// (`208`), truecolor (`#ff8800`), modifiers (`bold`, `dim`, `italic`, `underline`) and `none`.
+/// Colors prefixed with `bg:` apply to the background.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Style {
+    sgr: Vec<String>,
+}
+
+impl Style {
+    /// SGR parameters of the escape sequence, e.g. `1;31`. Empty if the style has no effect.
+    pub fn sgr(&self) -> String {
+        self.sgr.join(";")
+    }
+
+    fn parse_color(color: &str, background: bool) -> Option<String> {
+        const NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+        let (base, extended) = if background { (40, 48) } else { (30, 38) };
+        if let Some(index) = NAMES.iter().position(|n| *n == color) {
+            Some((base + index).to_string())
+        } else if let Some(index) = color
+            .strip_prefix("bright-")
+            .and_then(|c| NAMES.iter().position(|n| *n == c))
+        {
+            Some((base + 60 + index).to_string())
+        } else if let Ok(index) = color.parse::<u8>() {
+            Some(format!("{extended};5;{index}"))
+        } else if let Some(hex) = color.strip_prefix('#').filter(|h| h.len() == 6) {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            Some(format!("{extended};2;{};{};{}", channel(0)?, channel(2)?, channel(4)?))
+        } else {
+            None
+        }
+    }
+}
+
+impl TryFrom<String> for Style {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let mut sgr = vec![];
+        for word in text.split_whitespace() {
+            let code = match word {
+                "none" => continue,
+                "bold" => "1".to_string(),
+                "dim" => "2".to_string(),
+                "italic" => "3".to_string(),
+                "underline" => "4".to_string(),
+                _ => match word.strip_prefix("bg:") {
+                    Some(color) => Style::parse_color(color, true),
+                    None => Style::parse_color(word, false),
+                }
+                .ok_or_else(|| format!("invalid color '{word}'"))?,
+            };
+            sgr.push(code);
+        }
+        Ok(Style { sgr })
+    }
 }
 
 impl Default for Prompt {
@@ -155,6 +442,42 @@ impl Default for Prompt {
             zsh_use_rps1: false,
             fish_use_rprompt: false,
             xonsh_use_right_prompt: false,
+            nu_use_right_prompt: false,
+            show_tags: false,
+            format: None,
+            colors: BTreeMap::new(),
+            overrides: vec![],
+            title: Title::default(),
+        }
+    }
+}
+
+/// Title set by kubie shells when the context or namespace changes. The previous title is restored
+/// when the kubie shell exits.
+#[derive(Debug, Deserialize)]
+pub struct Title {
+    /// Set the terminal title with an OSC escape sequence.
+    #[serde(default = "def_bool_false")]
+    pub terminal: bool,
+    /// Rename the tmux window when running inside of tmux.
+    #[serde(default = "def_bool_false")]
+    pub tmux: bool,
+    #[serde(default = "def_title_format")]
+    pub format: String,
+}
+
+impl Title {
+    pub fn is_enabled(&self) -> bool {
+        self.terminal || self.tmux
+    }
+}
+
+impl Default for Title {
+    fn default() -> Self {
+        Title {
+            terminal: false,
+            tmux: false,
+            format: def_title_format(),
         }
     }
 }
@@ -180,7 +503,7 @@ impl ContextHeaderBehavior {
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 pub struct Behavior {
     #[serde(default)]
     pub validate_namespaces: ValidateNamespacesBehavior,
@@ -188,6 +511,46 @@ pub struct Behavior {
     pub print_context_in_exec: ContextHeaderBehavior,
     #[serde(default = "def_bool_false")]
     pub allow_multiple_context_patterns: bool,
+    /// Time after which the files written by `kubie export` and `kubie env` are removed, unless
+    /// they are still in use.
+    #[serde(default = "def_export_ttl", deserialize_with = "deserialize_duration")]
+    pub export_ttl: Duration,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub context_matching: ContextMatching,
+}
+
+impl Behavior {
+    /// Whether `kubie ctx` and `kubie ns` switch the global kubeconfig instead of spawning a shell.
+    /// Kubie shells are always switched in place, even in global mode.
+    pub fn is_global(&self) -> bool {
+        self.mode == Mode::Global && !vars::is_kubie_active()
+    }
+}
+
+impl Default for Behavior {
+    fn default() -> Self {
+        Behavior {
+            validate_namespaces: ValidateNamespacesBehavior::default(),
+            print_context_in_exec: ContextHeaderBehavior::default(),
+            allow_multiple_context_patterns: false,
+            export_ttl: def_export_ttl(),
+            mode: Mode::default(),
+            context_matching: ContextMatching::default(),
+        }
+    }
+}
+
+/// How `kubie ctx` and `kubie ns` behave outside of a kubie shell.
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Spawn a kubie shell.
+    #[default]
+    Shell,
+    /// Switch a single kubeconfig file in the data dir, which `KUBECONFIG` points at.
+    Global,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -208,12 +571,55 @@ impl ValidateNamespacesBehavior {
     }
 }
 
+/// How `kubie ctx <name>` matches the name against the contexts when no context has this exact name.
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextMatching {
+    #[default]
+    Exact,
+    /// The context name contains the query.
+    Partial,
+    /// The characters of the query appear in order in the context name, ignoring case.
+    Fuzzy,
+}
+
+impl ContextMatching {
+    pub fn matches(&self, context_name: &str, query: &str) -> bool {
+        match self {
+            ContextMatching::Exact => context_name == query,
+            ContextMatching::Partial => context_name.contains(query),
+            ContextMatching::Fuzzy => {
+                let mut chars = context_name.chars().flat_map(char::to_lowercase);
+                query
+                    .chars()
+                    .flat_map(char::to_lowercase)
+                    .all(|q| chars.any(|c| c == q))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct Hooks {
     #[serde(default)]
     pub start_ctx: String,
     #[serde(default)]
     pub stop_ctx: String,
+    #[serde(default)]
+    pub on_ctx_change: String,
+    #[serde(default)]
+    pub on_ns_change: String,
+}
+
+/// Hooks of the contexts matching a context settings entry.
+#[derive(Debug, Deserialize, Default)]
+pub struct ContextHooks {
+    /// Run when a kubie shell is spawned in the context or switched to it.
+    #[serde(default)]
+    pub on_enter: String,
+    /// Run when a kubie shell in the context exits or is switched to another context.
+    #[serde(default)]
+    pub on_leave: String,
 }
 
 fn def_bool_true() -> bool {
@@ -224,6 +630,20 @@ fn def_bool_false() -> bool {
     false
 }
 
+fn def_export_ttl() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+/// Deserialize a duration written in a human-friendly format such as `30m` or `1h 30m`.
+fn deserialize_duration<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    humantime::parse_duration(&text).map_err(serde::de::Error::custom)
+}
+
+fn def_title_format() -> String {
+    "{ctx}/{ns}".into()
+}
+
 #[test]
 fn test_expanduser() {
     assert_eq!(
@@ -231,3 +651,118 @@ fn test_expanduser() {
         format!("{}/hello/world/*.foo", home_dir())
     );
 }
+
+#[test]
+fn test_context_metadata_merge() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+contexts:
+  - pattern: "prod-*"
+    description: Production
+    tags:
+      env: prod
+      region: us
+  - pattern: "prod-eu"
+    owner: payments
+    tags:
+      region: eu
+"#,
+    )
+    .unwrap();
+
+    let metadata = settings.context_metadata("prod-eu");
+    assert_eq!(metadata.description.as_deref(), Some("Production"));
+    assert_eq!(metadata.owner.as_deref(), Some("payments"));
+    assert_eq!(metadata.tags_string(), "env=prod,region=eu");
+
+    let metadata = settings.context_metadata("staging");
+    assert!(metadata.tags.is_empty());
+}
+
+#[test]
+fn test_context_env() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+contexts:
+  - pattern: "eks-*"
+    env:
+      AWS_PROFILE: default
+      HELM_KUBECONTEXT: "${ctx}"
+  - pattern: "eks-prod"
+    env:
+      AWS_PROFILE: prod
+      ARGOCD_NAMESPACE: "argocd-${ns}"
+"#,
+    )
+    .unwrap();
+
+    let env = settings.context_env("eks-prod", "payments");
+    assert_eq!(env["AWS_PROFILE"], "prod");
+    assert_eq!(env["HELM_KUBECONTEXT"], "eks-prod");
+    assert_eq!(env["ARGOCD_NAMESPACE"], "argocd-payments");
+    assert!(settings.context_env("gke-prod", "default").is_empty());
+}
+
+#[test]
+fn test_shell_setting() {
+    let settings: Settings = serde_yaml::from_str("shell: zsh").unwrap();
+    assert!(matches!(settings.shell, Some(ShellSetting::Name(name)) if name == "zsh"));
+
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+shell:
+  command: osh --rcfile {rcfile}
+  rc: PS1="{prompt} $PS1"
+"#,
+    )
+    .unwrap();
+    assert!(matches!(settings.shell, Some(ShellSetting::Custom(custom)) if custom.command == "osh --rcfile {rcfile}"));
+}
+
+#[test]
+fn test_style_parse() {
+    let style = |s: &str| Style::try_from(s.to_string()).map(|s| s.sgr());
+    assert_eq!(style("red").unwrap(), "31");
+    assert_eq!(style("bold bright-blue").unwrap(), "1;94");
+    assert_eq!(style("208 bg:#102030").unwrap(), "38;5;208;48;2;16;32;48");
+    assert_eq!(style("none").unwrap(), "");
+    assert!(style("redish").is_err());
+    assert!(style("#12345").is_err());
+}
+
+#[test]
+fn test_prompt_style_overrides() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+prompt:
+  colors:
+    ctx: yellow
+    tags: cyan
+  overrides:
+    - pattern: "prod-*"
+      colors:
+        ctx: bold red
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(settings.prompt.style("prod-eu", "ctx").sgr(), "1;31");
+    assert_eq!(settings.prompt.style("prod-eu", "alias").sgr(), "1;31");
+    assert_eq!(settings.prompt.style("staging", "ctx").sgr(), "33");
+    assert_eq!(settings.prompt.style("staging", "ns").sgr(), "32");
+    assert_eq!(settings.prompt.style("staging", "tag:env").sgr(), "36");
+    assert_eq!(settings.prompt.style("staging", "user").sgr(), "");
+}
+
+#[test]
+fn test_context_matching() {
+    assert!(ContextMatching::Exact.matches("prod-eu", "prod-eu"));
+    assert!(!ContextMatching::Exact.matches("prod-eu", "prod"));
+
+    assert!(ContextMatching::Partial.matches("prod-eu", "od-e"));
+    assert!(!ContextMatching::Partial.matches("prod-eu", "peu"));
+
+    assert!(ContextMatching::Fuzzy.matches("prod-eu", "peu"));
+    assert!(ContextMatching::Fuzzy.matches("prod-eu", "PrEu"));
+    assert!(!ContextMatching::Fuzzy.matches("prod-eu", "eup"));
+}
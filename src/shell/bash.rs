@@ -51,6 +51,30 @@ function __kubie_cmd_pre_exec__() {{
 }}
 
 trap '__kubie_cmd_pre_exec__' DEBUG
+
+# Load the variables kubie updates when the context or namespace is switched in place,
+# before any other prompt command so that prompt themes see the up to date values.
+function __kubie_sync_env__() {{
+    if [[ -f "$KUBIE_ENV_FILE" ]] ; then
+        source "$KUBIE_ENV_FILE"
+    fi
+}}
+
+# Set the terminal title and tmux window name, see the title section of the prompt settings.
+function __kubie_set_title__() {{
+    if [[ -z "$KUBIE_TITLE" ]] ; then
+        return
+    fi
+    if [[ "$KUBIE_TITLE_TERMINAL" == "1" ]] ; then
+        printf '\033]2;%s\007' "$KUBIE_TITLE"
+    fi
+    if [[ "$KUBIE_TITLE_TMUX" == "1" && -n "$TMUX" && "$KUBIE_TITLE" != "$__kubie_tmux_title__" ]] ; then
+        __kubie_tmux_title__="$KUBIE_TITLE"
+        tmux rename-window -t "$TMUX_PANE" "$KUBIE_TITLE"
+    fi
+}}
+
+PROMPT_COMMAND="__kubie_sync_env__;__kubie_set_title__${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
 "#
     )?;
 
@@ -80,23 +104,5 @@ unset KUBIE_PROMPT
     let mut child = cmd.spawn()?;
     child.wait()?;
 
-    if !info.settings.hooks.start_ctx.is_empty() {
-        let temp_exit_hook_file = tempfile::Builder::new()
-            .prefix("kubie-bash-exit-hook")
-            .suffix(".bash")
-            .tempfile()?;
-        let mut temp_exit_hook_file_buf = BufWriter::new(temp_exit_hook_file.as_file());
-
-        write!(temp_exit_hook_file_buf, "{}", info.settings.hooks.stop_ctx)?;
-
-        temp_exit_hook_file_buf.flush()?;
-        let mut exit_cmd = Command::new("bash");
-        exit_cmd.arg(temp_exit_hook_file.path());
-        info.env_vars.apply(&mut exit_cmd);
-
-        let mut child = exit_cmd.spawn()?;
-        child.wait()?;
-    }
-
     Ok(())
 }
@@ -0,0 +1,46 @@
+use std::env;
+use std::io::{BufWriter, Write};
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+
+use super::ShellSpawnInfo;
+use crate::settings::CustomShell;
+
+/// Replace the placeholders of the custom shell's rc template.
+fn render_rc(info: &ShellSpawnInfo, rc: &str) -> Result<String> {
+    let current_exe_path = env::current_exe()?;
+    let current_exe_path_str = current_exe_path
+        .to_str()
+        .ok_or_else(|| anyhow!("Binary path is not unicode"))?;
+    let prompt = if info.settings.prompt.disable { "" } else { &info.prompt };
+
+    Ok(rc.replace("{prompt}", prompt).replace("{kubie}", current_exe_path_str))
+}
+
+/// Spawn a shell kubie does not know. Kubie's variables are set, but the rc file is the only way
+/// to integrate with the shell: kubie does not install a `KUBECONFIG` guard nor run hooks.
+pub fn spawn_shell(info: &ShellSpawnInfo, custom: &CustomShell) -> Result<()> {
+    let temp_rc_file = tempfile::Builder::new().prefix("kubie-rc").tempfile()?;
+    let mut temp_rc_file_buf = BufWriter::new(temp_rc_file.as_file());
+    write!(temp_rc_file_buf, "{}", render_rc(info, &custom.rc)?)?;
+    temp_rc_file_buf.flush()?;
+
+    let rc_path = temp_rc_file
+        .path()
+        .to_str()
+        .ok_or_else(|| anyhow!("Temp file path is not unicode"))?;
+    let args = shlex::split(&custom.command)
+        .filter(|args| !args.is_empty())
+        .ok_or_else(|| anyhow!("Invalid custom shell command: {}", custom.command))?;
+    let args: Vec<_> = args.iter().map(|arg| arg.replace("{rcfile}", rc_path)).collect();
+
+    let mut cmd = Command::new(&args[0]);
+    cmd.args(&args[1..]);
+    info.env_vars.apply(&mut cmd);
+
+    let mut child = cmd.spawn()?;
+    child.wait()?;
+
+    Ok(())
+}
@@ -1,15 +1,22 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str;
 
 use anyhow::{anyhow, Context, Result};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
 pub enum ShellKind {
     Bash,
     Fish,
     Xonsh,
     Zsh,
     Nu,
+    #[clap(alias = "powershell")]
+    Pwsh,
+    Elvish,
 }
 
 impl ShellKind {
@@ -20,41 +27,111 @@ impl ShellKind {
             "xonsh" | "python" => ShellKind::Xonsh,
             "zsh" => ShellKind::Zsh,
             "nu" => ShellKind::Nu,
+            "pwsh" | "powershell" => ShellKind::Pwsh,
+            "elvish" => ShellKind::Elvish,
             _ => return None,
         })
     }
+
+    /// Name of the shell, as exported in `KUBIE_SHELL`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShellKind::Bash => "bash",
+            ShellKind::Fish => "fish",
+            ShellKind::Xonsh => "xonsh",
+            ShellKind::Zsh => "zsh",
+            ShellKind::Nu => "nu",
+            ShellKind::Pwsh => "pwsh",
+            ShellKind::Elvish => "elvish",
+        }
+    }
+}
+
+/// Source of information about the running processes.
+trait ProcessTable {
+    fn parent_of(&self, pid: u32) -> Result<u32>;
+    fn command_of(&self, pid: u32) -> Result<String>;
 }
 
-fn run_ps(args: &[&str]) -> Result<Vec<String>> {
-    let result = Command::new("ps").args(args).output().context("Could not spawn ps")?;
+/// Process table read with the `ps` command, used where `/proc` is not available.
+struct Ps;
+
+impl Ps {
+    fn run(args: &[&str]) -> Result<Vec<String>> {
+        let result = Command::new("ps").args(args).output().context("Could not spawn ps")?;
+
+        if !result.status.success() {
+            let stderr = str::from_utf8(&result.stderr).unwrap_or("Could not decode stderr of ps as utf-8");
+            return Err(anyhow!("Error calling ps: {}", stderr));
+        }
+
+        let text = str::from_utf8(&result.stdout)?;
+        Ok(text.split('\n').filter(|x| !x.is_empty()).map(String::from).collect())
+    }
+}
 
-    if !result.status.success() {
-        let stderr = str::from_utf8(&result.stderr).unwrap_or("Could not decode stderr of ps as utf-8");
-        return Err(anyhow!("Error calling ps: {}", stderr));
+impl ProcessTable for Ps {
+    fn parent_of(&self, pid: u32) -> Result<u32> {
+        let lines = Ps::run(&["-o", "ppid=", &pid.to_string()])?;
+        let line = lines
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Could not get parent pid of pid={}", pid))?;
+        line.trim()
+            .parse()
+            .with_context(|| format!("Invalid parent pid of pid={pid}"))
     }
 
-    let text = str::from_utf8(&result.stdout)?;
-    Ok(text.split('\n').filter(|x| !x.is_empty()).map(String::from).collect())
+    fn command_of(&self, pid: u32) -> Result<String> {
+        let lines = Ps::run(&["-o", "args=", &pid.to_string()])?;
+        lines
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Could not get command of pid={}", pid))
+    }
 }
 
-fn parent_of(pid: &str) -> Result<String> {
-    let lines = run_ps(&["-o", "ppid=", pid])?;
-    lines
-        .into_iter()
-        .next()
-        .map(|x| x.trim().to_string())
-        .ok_or_else(|| anyhow!("Could not get parent pid of pid={}", pid))
+/// Process table read from a procfs mount such as `/proc`.
+struct ProcFs {
+    root: PathBuf,
 }
 
-fn command_of(pid: &str) -> Result<String> {
-    let lines = run_ps(&["-o", "args=", pid])?;
-    lines
-        .into_iter()
-        .next()
-        .ok_or_else(|| anyhow!("Could not get command of pid={}", pid))
+impl ProcessTable for ProcFs {
+    fn parent_of(&self, pid: u32) -> Result<u32> {
+        let path = self.root.join(pid.to_string()).join("stat");
+        let stat = fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        // The command name is between parentheses and can contain spaces and parentheses itself,
+        // the fields after the last parenthesis are the state and the parent pid.
+        stat.rsplit_once(')')
+            .and_then(|(_, fields)| fields.split_whitespace().nth(1))
+            .and_then(|ppid| ppid.parse().ok())
+            .ok_or_else(|| anyhow!("Could not parse {}", path.display()))
+    }
+
+    fn command_of(&self, pid: u32) -> Result<String> {
+        let path = self.root.join(pid.to_string()).join("cmdline");
+        let cmdline = fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        let args: Vec<_> = cmdline
+            .split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(String::from_utf8_lossy)
+            .collect();
+        Ok(args.join(" "))
+    }
+}
+
+fn process_table() -> Box<dyn ProcessTable> {
+    let proc_root = Path::new("/proc");
+    if cfg!(target_os = "linux") && proc_root.join("self").exists() {
+        Box::new(ProcFs {
+            root: proc_root.to_path_buf(),
+        })
+    } else {
+        Box::new(Ps)
+    }
 }
 
-fn parse_command(cmd: &str) -> &str {
+pub fn parse_command(cmd: &str) -> &str {
     let first_space = cmd.find(' ').unwrap_or(cmd.len());
     let binary_path = &cmd[..first_space];
     let last_path_sep = binary_path.rfind('/').map(|x| x + 1).unwrap_or(0);
@@ -64,33 +141,158 @@ fn parse_command(cmd: &str) -> &str {
         .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
 }
 
+/// Ancestor of the kubie process.
+#[derive(Debug)]
+pub struct Ancestor {
+    pub pid: u32,
+    pub command: String,
+    pub kind: Option<ShellKind>,
+}
+
+/// Walk up the process tree from the parent of the given process, stopping at the first known
+/// shell unless `full` is set.
+fn ancestry_of(table: &dyn ProcessTable, pid: u32, full: bool) -> Result<Vec<Ancestor>> {
+    let mut ancestors = vec![];
+    let mut parent_pid = table.parent_of(pid)?;
+    while parent_pid > 1 {
+        let command = table.command_of(parent_pid)?;
+        let kind = ShellKind::from_str(parse_command(&command));
+        ancestors.push(Ancestor {
+            pid: parent_pid,
+            command,
+            kind,
+        });
+        if kind.is_some() && !full {
+            break;
+        }
+        parent_pid = table.parent_of(parent_pid)?;
+    }
+    Ok(ancestors)
+}
+
+/// All the ancestors of the kubie process, up to the init process.
+pub fn ancestry() -> Result<Vec<Ancestor>> {
+    ancestry_of(process_table().as_ref(), std::process::id(), true)
+}
+
+/// Process which invoked the given process. The subshells forked by a shell for a command
+/// substitution such as `$(kubie export ...)` run the same command as the shell and exit with the
+/// process, they are skipped.
+fn caller_of(table: &dyn ProcessTable, pid: u32) -> Result<u32> {
+    let mut caller = table.parent_of(pid)?;
+    loop {
+        let parent = table.parent_of(caller)?;
+        if parent <= 1 || table.command_of(parent)? != table.command_of(caller)? {
+            return Ok(caller);
+        }
+        caller = parent;
+    }
+}
+
+/// Process which invoked kubie, if it can be found.
+pub fn caller_pid() -> Option<u32> {
+    caller_of(process_table().as_ref(), std::process::id()).ok()
+}
+
+fn detect_from_ancestry(table: &dyn ProcessTable, pid: u32) -> Result<ShellKind> {
+    ancestry_of(table, pid, false)?
+        .into_iter()
+        .find_map(|a| a.kind)
+        .ok_or_else(|| anyhow!("Could not detect shell in use"))
+}
+
+/// Detect the shell from the SHELL environment variable.
+pub fn detect_from_env() -> Option<ShellKind> {
+    let shell = env::var("SHELL").ok()?;
+    ShellKind::from_str(parse_command(&shell))
+}
+
 /// Detect from which kind of shell kubie was spawned.
 ///
 /// This function walks up the process tree and finds all the ancestors to kubie.
 /// If any of kubie's ancestor is a known shell, we have found which shell is in
 /// use.
 ///
-/// This functions depends on the `ps` command being installed and available in
-/// the PATH variable.
+/// On Linux the process tree is read from `/proc`, elsewhere this function depends on the `ps`
+/// command being installed and available in the PATH variable.
 ///
 /// The SHELL environment variable corresponds to the user's configured SHELL, not
-/// the shell currently in use.
+/// the shell currently in use. It is only used as a fallback when the process tree
+/// does not contain a known shell.
 pub fn detect() -> Result<ShellKind> {
-    let kubie_pid = format!("{}", std::process::id());
-    let mut parent_pid = parent_of(&kubie_pid)?;
-    loop {
-        if parent_pid == "1" {
-            return Err(anyhow!("Could not detect shell in use"));
-        }
+    let error = match detect_from_ancestry(process_table().as_ref(), std::process::id()) {
+        Ok(kind) => return Ok(kind),
+        Err(err) => err,
+    };
 
-        let cmd = command_of(&parent_pid)?;
-        let name = parse_command(&cmd);
-        if let Some(kind) = ShellKind::from_str(name) {
-            return Ok(kind);
+    match detect_from_env() {
+        Some(kind) => {
+            eprintln!(
+                "Warning: {}, falling back to the SHELL environment variable ({}).",
+                error,
+                kind.name()
+            );
+            Ok(kind)
         }
+        None => Err(error),
+    }
+}
 
-        parent_pid = parent_of(&parent_pid)?;
+#[cfg(test)]
+fn fake_proc(processes: &[(u32, &str, u32, &str)]) -> tempfile::TempDir {
+    let root = tempfile::tempdir().unwrap();
+    for (pid, comm, ppid, cmdline) in processes {
+        let dir = root.path().join(pid.to_string());
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("stat"), format!("{pid} ({comm}) S {ppid} {pid} {pid} 0 -1")).unwrap();
+        fs::write(dir.join("cmdline"), cmdline.replace(' ', "\0") + "\0").unwrap();
     }
+    root
+}
+
+#[test]
+fn test_procfs_detect() {
+    let root = fake_proc(&[
+        (40, "tmux: server", 1, "tmux"),
+        (50, "zsh", 40, "-zsh"),
+        (60, "bash", 50, "/usr/bin/bash --rcfile /tmp/kubie-bashrc"),
+        (70, "kubie", 60, "kubie ctx"),
+    ]);
+    let table = ProcFs {
+        root: root.path().to_path_buf(),
+    };
+    assert_eq!(detect_from_ancestry(&table, 70).unwrap(), ShellKind::Bash);
+
+    let ancestry = ancestry_of(&table, 70, true).unwrap();
+    let pids: Vec<_> = ancestry.iter().map(|a| a.pid).collect();
+    assert_eq!(pids, vec![60, 50, 40]);
+    assert_eq!(ancestry[0].command, "/usr/bin/bash --rcfile /tmp/kubie-bashrc");
+    assert_eq!(ancestry[2].kind, None);
+}
+
+#[test]
+fn test_procfs_parens_in_comm() {
+    let root = fake_proc(&[(50, "weird) (name", 1, "sh"), (60, "kubie", 50, "kubie")]);
+    let table = ProcFs {
+        root: root.path().to_path_buf(),
+    };
+    assert_eq!(table.parent_of(50).unwrap(), 1);
+    assert!(detect_from_ancestry(&table, 60).is_err());
+}
+
+#[test]
+fn test_procfs_caller() {
+    let root = fake_proc(&[
+        (50, "bash", 1, "-bash"),
+        (60, "bash", 50, "-bash"),
+        (70, "kubie", 60, "kubie export prod-eu default"),
+        (80, "kubie", 50, "kubie env prod-eu default"),
+    ]);
+    let table = ProcFs {
+        root: root.path().to_path_buf(),
+    };
+    assert_eq!(caller_of(&table, 70).unwrap(), 50);
+    assert_eq!(caller_of(&table, 80).unwrap(), 50);
 }
 
 #[test]
@@ -127,3 +329,19 @@ fn test_parse_command_versioned_intepreter() {
 fn test_parse_command_nu() {
     assert_eq!(parse_command("/bin/nu"), "nu");
 }
+
+#[test]
+fn test_parse_command_pwsh() {
+    assert_eq!(
+        ShellKind::from_str(parse_command("/opt/microsoft/powershell/7/pwsh -NoExit")),
+        Some(ShellKind::Pwsh)
+    );
+}
+
+#[test]
+fn test_parse_command_elvish() {
+    assert_eq!(
+        ShellKind::from_str(parse_command("/usr/local/bin/elvish")),
+        Some(ShellKind::Elvish)
+    );
+}
@@ -0,0 +1,94 @@
+use std::io::{BufWriter, Write};
+use std::process::Command;
+
+use anyhow::Result;
+
+use super::ShellSpawnInfo;
+
+/// Quote a value as an elvish single-quoted string.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
+    let temp_rc_file = tempfile::Builder::new().prefix("kubie-rc").suffix(".elv").tempfile()?;
+    let mut temp_rc_file_buf = BufWriter::new(temp_rc_file.as_file());
+
+    write!(
+        temp_rc_file_buf,
+        r#"
+use os
+
+# Elvish only loads a single rc file. Evaluate the user's rc file and make its variables and
+# functions available in the interactive namespace.
+var __kubie_config_home = (if (has-env XDG_CONFIG_HOME) {{ put $E:XDG_CONFIG_HOME }} else {{ put ~/.config }})
+for rc [$__kubie_config_home/elvish/rc.elv ~/.elvish/rc.elv] {{
+    if (os:is-regular $rc) {{
+        eval (slurp < $rc) &on-end={{|ns|
+            var vars = [&]
+            for name [(keys $ns)] {{
+                set vars[$name] = $ns[$name]
+            }}
+            edit:add-vars $vars
+        }}
+        break
+    }}
+}}
+
+# Set the proper KUBECONFIG variable before each command runs,
+# to prevent the user from overwriting it.
+set edit:after-readline = [$@edit:after-readline {{|line|
+    set-env KUBECONFIG $E:KUBIE_KUBECONFIG
+}}]
+
+set edit:before-readline = [{{
+    # Load the variables kubie updates when the context or namespace is switched in place.
+    if (os:is-regular $E:KUBIE_ENV_FILE) {{
+        eval (slurp < $E:KUBIE_ENV_FILE)
+    }}
+
+    # Set the terminal title and tmux window name, see the title section of the prompt settings.
+    if (has-env KUBIE_TITLE) {{
+        if (eq $E:KUBIE_TITLE_TERMINAL 1) {{
+            print "\e]2;"$E:KUBIE_TITLE"\a" > /dev/tty
+        }}
+        if (and (eq $E:KUBIE_TITLE_TMUX 1) (has-env TMUX) (not-eq $E:KUBIE_TITLE $E:KUBIE_TMUX_TITLE)) {{
+            set-env KUBIE_TMUX_TITLE $E:KUBIE_TITLE
+            tmux rename-window -t $E:TMUX_PANE $E:KUBIE_TITLE
+        }}
+    }}
+}} $@edit:before-readline]
+"#
+    )?;
+
+    if !info.settings.prompt.disable {
+        write!(
+            temp_rc_file_buf,
+            r#"
+var __kubie_original_prompt = $edit:prompt
+set edit:prompt = {{
+    {}
+    print ' '
+    $__kubie_original_prompt
+}}
+"#,
+            info.prompt,
+        )?;
+    }
+
+    if !info.settings.hooks.start_ctx.is_empty() {
+        write!(temp_rc_file_buf, "{}", info.settings.hooks.start_ctx)?;
+    }
+
+    temp_rc_file_buf.flush()?;
+
+    let mut cmd = Command::new("elvish");
+    cmd.arg("-rc");
+    cmd.arg(temp_rc_file.path());
+    info.env_vars.apply(&mut cmd);
+
+    let mut child = cmd.spawn()?;
+    child.wait()?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use super::ShellKind;
+
+fn quote_posix(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn quote_fish(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Render the variables in the syntax of the given shell. A variable without a value is unset.
+///
+/// Shells which cannot source a script at runtime read a JSON object instead.
+pub fn render(kind: ShellKind, vars: &BTreeMap<String, Option<String>>) -> Result<String> {
+    let mut script = String::new();
+    match kind {
+        ShellKind::Bash | ShellKind::Zsh => {
+            for (name, value) in vars {
+                match value {
+                    Some(value) => script.push_str(&format!("export {}={}\n", name, quote_posix(value))),
+                    None => script.push_str(&format!("unset {name}\n")),
+                }
+            }
+        }
+        ShellKind::Fish => {
+            for (name, value) in vars {
+                match value {
+                    Some(value) => script.push_str(&format!("set -gx {} {}\n", name, quote_fish(value))),
+                    None => script.push_str(&format!("set -e {name}\n")),
+                }
+            }
+        }
+        ShellKind::Pwsh => {
+            for (name, value) in vars {
+                match value {
+                    Some(value) => script.push_str(&format!("$env:{} = {}\n", name, super::pwsh::quote(value))),
+                    None => script.push_str(&format!("Remove-Item -ErrorAction Ignore Env:{name}\n")),
+                }
+            }
+        }
+        ShellKind::Elvish => {
+            for (name, value) in vars {
+                match value {
+                    Some(value) => script.push_str(&format!("set-env {} {}\n", name, super::elvish::quote(value))),
+                    None => script.push_str(&format!("unset-env {name}\n")),
+                }
+            }
+        }
+        ShellKind::Xonsh | ShellKind::Nu => {
+            script = serde_json::to_string(vars)?;
+        }
+    }
+    Ok(script)
+}
+
+/// Write the env file which the shell's hooks load before each prompt.
+pub fn write(kind: ShellKind, path: &Path, vars: &BTreeMap<String, Option<String>>) -> Result<()> {
+    fs::write(path, render(kind, vars)?).with_context(|| format!("Could not write env file {}", path.display()))
+}
+
+#[cfg(test)]
+fn test_vars() -> BTreeMap<String, Option<String>> {
+    let mut vars = BTreeMap::new();
+    vars.insert("KUBIE_CONTEXT".into(), Some("it's".into()));
+    vars.insert("KUBIE_OLD".into(), None);
+    vars
+}
+
+#[test]
+fn test_render_posix() {
+    assert_eq!(
+        render(ShellKind::Bash, &test_vars()).unwrap(),
+        "export KUBIE_CONTEXT='it'\\''s'\nunset KUBIE_OLD\n"
+    );
+}
+
+#[test]
+fn test_render_fish() {
+    assert_eq!(
+        render(ShellKind::Fish, &test_vars()).unwrap(),
+        "set -gx KUBIE_CONTEXT 'it\\'s'\nset -e KUBIE_OLD\n"
+    );
+}
+
+#[test]
+fn test_render_pwsh() {
+    assert_eq!(
+        render(ShellKind::Pwsh, &test_vars()).unwrap(),
+        "$env:KUBIE_CONTEXT = 'it''s'\nRemove-Item -ErrorAction Ignore Env:KUBIE_OLD\n"
+    );
+}
+
+#[test]
+fn test_render_json() {
+    assert_eq!(
+        render(ShellKind::Nu, &test_vars()).unwrap(),
+        r#"{"KUBIE_CONTEXT":"it's","KUBIE_OLD":null}"#
+    );
+}
@@ -16,6 +16,24 @@ function kubie_preexec --on-event fish_preexec
     set -xg KUBECONFIG "$KUBIE_KUBECONFIG"
 end
 
+# Load the variables kubie updates when the context or namespace is switched in place.
+function kubie_sync_env --on-event fish_prompt
+    test -f "$KUBIE_ENV_FILE"; and source "$KUBIE_ENV_FILE"
+
+    # Rename the tmux window, see the title section of the prompt settings.
+    if test "$KUBIE_TITLE_TMUX" = "1" -a -n "$TMUX" -a -n "$KUBIE_TITLE" -a "$KUBIE_TITLE" != "$kubie_tmux_title"
+        set -g kubie_tmux_title "$KUBIE_TITLE"
+        tmux rename-window -t "$TMUX_PANE" "$KUBIE_TITLE"
+    end
+end
+
+# Fish sets the terminal title with the output of fish_title before each prompt.
+if test "$KUBIE_TITLE_TERMINAL" = "1"
+    function fish_title
+        echo $KUBIE_TITLE
+    end
+end
+
 if test "$KUBIE_PROMPT_DISABLE" = "0"
     # The general idea behind the prompt substitions is to save the existing
     # prompt's output _before_ anything else is run. This is important since the
@@ -50,8 +68,11 @@ if test "$KUBIE_PROMPT_DISABLE" = "0"
         end
     end
 end
+
+{start_ctx}
     "#,
         prompt = info.prompt,
+        start_ctx = info.settings.hooks.start_ctx,
     ));
     info.env_vars.apply(&mut cmd);
 
@@ -0,0 +1,128 @@
+use std::io::{BufWriter, Write};
+use std::process::{Command, ExitStatus};
+
+use anyhow::Result;
+
+use super::{EnvVars, ShellKind};
+use crate::kubeconfig::KubeConfig;
+use crate::settings::{ContextHooks, Settings};
+
+fn extension(kind: ShellKind) -> &'static str {
+    match kind {
+        ShellKind::Bash => ".bash",
+        ShellKind::Fish => ".fish",
+        ShellKind::Xonsh => ".xsh",
+        ShellKind::Zsh => ".zsh",
+        ShellKind::Nu => ".nu",
+        ShellKind::Pwsh => ".ps1",
+        ShellKind::Elvish => ".elv",
+    }
+}
+
+fn command(kind: ShellKind) -> Command {
+    match kind {
+        ShellKind::Bash => Command::new("bash"),
+        ShellKind::Fish => Command::new("fish"),
+        ShellKind::Xonsh => Command::new("xonsh"),
+        ShellKind::Zsh => Command::new("zsh"),
+        ShellKind::Nu => Command::new("nu"),
+        ShellKind::Pwsh => {
+            let mut cmd = Command::new("pwsh");
+            cmd.args(["-NoProfile", "-File"]);
+            cmd
+        }
+        ShellKind::Elvish => Command::new("elvish"),
+    }
+}
+
+/// Run a hook script with the given shell. Hooks run in their own process, they cannot modify the
+/// state of the kubie shell. A hook which fails does not fail the command, the switch it reacts to
+/// has already happened.
+pub fn run_hook(kind: ShellKind, script: &str, env_vars: &EnvVars) {
+    if script.is_empty() {
+        return;
+    }
+
+    let run = || -> Result<ExitStatus> {
+        let temp_hook_file = tempfile::Builder::new()
+            .prefix("kubie-hook")
+            .suffix(extension(kind))
+            .tempfile()?;
+        let mut temp_hook_file_buf = BufWriter::new(temp_hook_file.as_file());
+        write!(temp_hook_file_buf, "{script}")?;
+        temp_hook_file_buf.flush()?;
+        drop(temp_hook_file_buf);
+
+        let mut cmd = command(kind);
+        cmd.arg(temp_hook_file.path());
+        env_vars.apply(&mut cmd);
+        Ok(cmd.status()?)
+    };
+
+    match run() {
+        Ok(status) if !status.success() => eprintln!("Warning: hook exited with {status}"),
+        Ok(_) => {}
+        Err(err) => eprintln!("Warning: could not run hook: {err}"),
+    }
+}
+
+fn run_context_hooks(
+    kind: ShellKind,
+    settings: &Settings,
+    config: &KubeConfig,
+    env_vars: &EnvVars,
+    hook: fn(&ContextHooks) -> &str,
+) {
+    let context_name = config.current_context.as_deref().unwrap_or("");
+    let mut env_vars = env_vars.clone();
+    env_vars.insert("KUBIE_CONTEXT", context_name);
+    env_vars.insert(
+        "KUBIE_NAMESPACE",
+        config.contexts[0].context.namespace.as_deref().unwrap_or("default"),
+    );
+
+    for context_settings in settings.context_settings(context_name) {
+        run_hook(kind, hook(&context_settings.hooks), &env_vars);
+    }
+}
+
+/// Run the `on_enter` hooks of the context settings matching the context of the kubeconfig.
+pub fn run_enter_hooks(kind: ShellKind, settings: &Settings, config: &KubeConfig, env_vars: &EnvVars) {
+    run_context_hooks(kind, settings, config, env_vars, |hooks| &hooks.on_enter)
+}
+
+/// Run the `on_leave` hooks of the context settings matching the context of the kubeconfig.
+pub fn run_leave_hooks(kind: ShellKind, settings: &Settings, config: &KubeConfig, env_vars: &EnvVars) {
+    run_context_hooks(kind, settings, config, env_vars, |hooks| &hooks.on_leave)
+}
+
+/// Run the hooks of a context or namespace switched in place in the current kubie shell.
+pub fn run_switch_hooks(settings: &Settings, old: &KubeConfig, new: &KubeConfig) {
+    // Outside of a kubie shell, in global mode, hooks run with the shell which called kubie.
+    let kind = match super::current_shell_kind().or_else(|| super::detect().ok()) {
+        Some(kind) => kind,
+        None => return,
+    };
+
+    let old_context = old.current_context.as_deref().unwrap_or("");
+    let new_context = new.current_context.as_deref().unwrap_or("");
+    let old_namespace = old.contexts[0].context.namespace.as_deref().unwrap_or("default");
+    let new_namespace = new.contexts[0].context.namespace.as_deref().unwrap_or("default");
+
+    let mut env_vars = EnvVars::new();
+    env_vars.insert("KUBIE_OLD_CONTEXT", old_context);
+    env_vars.insert("KUBIE_NEW_CONTEXT", new_context);
+    env_vars.insert("KUBIE_OLD_NAMESPACE", old_namespace);
+    env_vars.insert("KUBIE_NEW_NAMESPACE", new_namespace);
+    env_vars.insert("KUBIE_CONTEXT", new_context);
+    env_vars.insert("KUBIE_NAMESPACE", new_namespace);
+
+    if old_context != new_context {
+        run_leave_hooks(kind, settings, old, &env_vars);
+        run_hook(kind, &settings.hooks.on_ctx_change, &env_vars);
+        run_enter_hooks(kind, settings, new, &env_vars);
+    }
+    if old_namespace != new_namespace {
+        run_hook(kind, &settings.hooks.on_ns_change, &env_vars);
+    }
+}
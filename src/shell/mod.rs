@@ -1,24 +1,34 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::ffi::OsString;
+use std::path::PathBuf;
 use std::process::Command;
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 
-use self::detect::{detect, ShellKind};
+pub use self::detect::{ancestry, caller_pid, detect, detect_from_env, ShellKind};
+use crate::ioutil;
 use crate::kubeconfig::KubeConfig;
 use crate::session::Session;
-use crate::settings::Settings;
+use crate::settings::{CustomShell, Settings, ShellSetting};
 use crate::state;
 use crate::vars;
 
 mod bash;
+mod custom;
 mod detect;
+mod elvish;
+mod env_file;
 mod fish;
+pub mod hooks;
 mod nu;
-mod prompt;
+pub mod prompt;
+mod pwsh;
+mod title;
 mod xonsh;
 mod zsh;
 
+#[derive(Clone)]
 pub struct EnvVars<'n> {
     vars: HashMap<&'n str, OsString>,
 }
@@ -45,11 +55,109 @@ pub struct ShellSpawnInfo<'s, 'n> {
     prompt: String,
 }
 
-pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &Session) -> Result<()> {
-    let kind = match &settings.shell {
-        Some(shell) => ShellKind::from_str(shell).ok_or_else(|| anyhow!("Invalid shell setting: {}", shell))?,
-        None => detect()?,
+/// Variables exported in kubie shells which change when the context or namespace is switched in place,
+/// including the variables configured for the context in the settings.
+fn context_env_vars(settings: &Settings, config: &KubeConfig, depth: u32) -> BTreeMap<String, Option<String>> {
+    let context_name = config.current_context.as_deref().unwrap_or("");
+    let namespace_name = config.contexts[0].context.namespace.as_deref().unwrap_or("default");
+
+    let mut vars: BTreeMap<_, _> = settings
+        .context_env(context_name, namespace_name)
+        .into_iter()
+        .map(|(name, value)| (name, Some(value)))
+        .collect();
+    vars.insert("KUBIE_CONTEXT".into(), Some(context_name.into()));
+    vars.insert("KUBIE_NAMESPACE".into(), Some(namespace_name.into()));
+    if settings.prompt.title.is_enabled() {
+        vars.insert(
+            "KUBIE_TITLE".into(),
+            Some(prompt::render_title(settings, config, depth)),
+        );
+    }
+    vars
+}
+
+/// Render variables as statements which the given shell can evaluate. A variable without a value is
+/// unset. Nu and xonsh read a JSON object instead.
+pub fn render_env(kind: ShellKind, vars: &BTreeMap<String, Option<String>>) -> Result<String> {
+    env_file::render(kind, vars)
+}
+
+/// Kind of the current kubie shell, if any.
+pub fn current_shell_kind() -> Option<ShellKind> {
+    env::var("KUBIE_SHELL").ok().and_then(|s| ShellKind::from_str(&s))
+}
+
+/// Update the env file of the current kubie shell after its context or namespace was switched in
+/// place. The shell loads the env file before each prompt to keep its variables in sync. Variables
+/// of the previous context which the new context does not set are restored to the value they had
+/// before the shell was spawned, or unset.
+pub fn sync_env_file(
+    settings: &Settings,
+    session: &Session,
+    old_config: &KubeConfig,
+    config: &KubeConfig,
+) -> Result<()> {
+    let path = match env::var_os("KUBIE_ENV_FILE") {
+        Some(path) => PathBuf::from(path),
+        None => return Ok(()),
+    };
+    let kind = match current_shell_kind() {
+        Some(kind) => kind,
+        None => return Ok(()),
     };
+    let depth = vars::get_depth();
+    let mut env_vars = context_env_vars(settings, config, depth);
+    for name in context_env_vars(settings, old_config, depth).into_keys() {
+        let original = session.original_env(&name).map(String::from);
+        env_vars.entry(name).or_insert(original);
+    }
+    env_file::write(kind, &path, &env_vars)
+}
+
+/// Shell resolved from the settings, or detected if the settings do not specify one.
+enum Shell {
+    Known(ShellKind),
+    Custom(CustomShell),
+}
+
+impl Shell {
+    fn resolve(settings: &Settings) -> Result<Shell> {
+        match &settings.shell {
+            Some(ShellSetting::Custom(custom)) => Ok(Shell::Custom(custom.clone())),
+            Some(ShellSetting::Name(name)) => match ShellKind::from_str(name) {
+                Some(kind) => Ok(Shell::Known(kind)),
+                None => {
+                    eprintln!(
+                        "Warning: kubie does not know the shell {name}, spawning it without kubie's prompt and hooks."
+                    );
+                    Ok(Shell::Custom(CustomShell {
+                        command: name.clone(),
+                        rc: String::new(),
+                    }))
+                }
+            },
+            None => Ok(Shell::Known(detect()?)),
+        }
+    }
+
+    fn kind(&self) -> Option<ShellKind> {
+        match self {
+            Shell::Known(kind) => Some(*kind),
+            Shell::Custom(_) => None,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Shell::Known(kind) => kind.name().into(),
+            Shell::Custom(custom) => detect::parse_command(&custom.command).into(),
+        }
+    }
+}
+
+pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &Session) -> Result<()> {
+    let shell = Shell::resolve(settings)?;
 
     let temp_config_file = tempfile::Builder::new()
         .prefix("kubie-config")
@@ -61,11 +169,32 @@ pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &Session) -
         .prefix("kubie-session")
         .suffix(".json")
         .tempfile()?;
+    let mut session = session.clone();
+    session.record_original_env(settings.contexts.iter().flat_map(|c| c.env.keys()));
     session.save(Some(temp_session_file.path()))?;
 
     let depth = vars::get_depth();
     let next_depth = depth + 1;
 
+    let context_vars = context_env_vars(settings, &config, next_depth);
+    // Custom shells have no hook loading the env file.
+    let temp_env_file = match shell.kind() {
+        Some(kind) => {
+            let temp_env_file = tempfile::Builder::new()
+                .prefix("kubie-env")
+                .suffix(match kind {
+                    ShellKind::Xonsh | ShellKind::Nu => ".json",
+                    ShellKind::Pwsh => ".ps1",
+                    ShellKind::Elvish => ".elv",
+                    _ => ".sh",
+                })
+                .tempfile()?;
+            env_file::write(kind, temp_env_file.path(), &context_vars)?;
+            Some(temp_env_file)
+        }
+        None => None,
+    };
+
     let mut env_vars = EnvVars::new();
 
     // Pre-insert the KUBECONFIG variable into the shell.
@@ -77,6 +206,14 @@ pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &Session) -
     env_vars.insert("KUBIE_KUBECONFIG", temp_config_file.path());
     env_vars.insert("KUBIE_SESSION", temp_session_file.path());
     env_vars.insert("KUBIE_STATE", state::paths::state());
+    if let Some(temp_env_file) = &temp_env_file {
+        env_vars.insert("KUBIE_ENV_FILE", temp_env_file.path());
+    }
+    for (name, value) in &context_vars {
+        if let Some(value) = value {
+            env_vars.insert(name, value);
+        }
+    }
 
     env_vars.insert("KUBIE_PROMPT_DISABLE", if settings.prompt.disable { "1" } else { "0" });
     env_vars.insert(
@@ -95,36 +232,50 @@ pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &Session) -
             "0"
         },
     );
+    env_vars.insert(
+        "KUBIE_NU_USE_RIGHT_PROMPT",
+        if settings.prompt.nu_use_right_prompt { "1" } else { "0" },
+    );
+    env_vars.insert(
+        "KUBIE_TITLE_TERMINAL",
+        if settings.prompt.title.terminal { "1" } else { "0" },
+    );
+    env_vars.insert("KUBIE_TITLE_TMUX", if settings.prompt.title.tmux { "1" } else { "0" });
 
-    match kind {
-        ShellKind::Bash => {
-            env_vars.insert("KUBIE_SHELL", "bash");
-        }
-        ShellKind::Fish => {
-            env_vars.insert("KUBIE_SHELL", "fish");
-        }
-        ShellKind::Xonsh => {
-            env_vars.insert("KUBIE_SHELL", "xonsh");
-        }
-        ShellKind::Zsh => {
-            env_vars.insert("KUBIE_SHELL", "zsh");
-        }
-        ShellKind::Nu => {
-            env_vars.insert("KUBIE_SHELL", "nu");
-        }
-    }
+    env_vars.insert("KUBIE_SHELL", shell.name());
 
     let info = ShellSpawnInfo {
         settings,
         env_vars,
-        prompt: prompt::generate_ps1(settings, next_depth, kind),
+        prompt: prompt::generate_ps1(shell.kind()),
+    };
+
+    let kind = match shell {
+        Shell::Known(kind) => kind,
+        Shell::Custom(custom) => return custom::spawn_shell(&info, &custom),
     };
 
+    hooks::run_enter_hooks(kind, settings, &config, &info.env_vars);
+
+    // Restore the title which was set before entering the shell once it exits.
+    let title_guard = title::TitleGuard::new(&settings.prompt.title);
+
     match kind {
-        ShellKind::Bash => bash::spawn_shell(&info),
-        ShellKind::Fish => fish::spawn_shell(&info),
-        ShellKind::Xonsh => xonsh::spawn_shell(&info),
-        ShellKind::Zsh => zsh::spawn_shell(&info),
-        ShellKind::Nu => nu::spawn_shell(&info),
+        ShellKind::Bash => bash::spawn_shell(&info)?,
+        ShellKind::Fish => fish::spawn_shell(&info)?,
+        ShellKind::Xonsh => xonsh::spawn_shell(&info)?,
+        ShellKind::Zsh => zsh::spawn_shell(&info)?,
+        ShellKind::Nu => nu::spawn_shell(&info)?,
+        ShellKind::Pwsh => pwsh::spawn_shell(&info)?,
+        ShellKind::Elvish => elvish::spawn_shell(&info)?,
     }
+
+    drop(title_guard);
+
+    hooks::run_hook(kind, &settings.hooks.stop_ctx, &info.env_vars);
+
+    // The context may have been switched in place while the shell was running.
+    let final_config: KubeConfig = ioutil::read_yaml(temp_config_file.path())?;
+    hooks::run_leave_hooks(kind, settings, &final_config, &info.env_vars);
+    Ok(())
 }
@@ -1,40 +1,101 @@
-use anyhow::Result;
+use std::io::{BufWriter, Write};
 use std::process::Command;
 
-use crate::shell::ShellSpawnInfo;
+use anyhow::Result;
+
+use super::ShellSpawnInfo;
+
+/// Quote a value as a nushell string literal.
+pub fn quote(value: &str) -> String {
+    format!(r#""{}""#, value.replace('\\', r"\\").replace('"', r#"\""#))
+}
 
 pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
-    // let dir = tempdir()?;
+    let temp_config_file = tempfile::Builder::new()
+        .prefix("kubie-config")
+        .suffix(".nu")
+        .tempfile()?;
+    let mut temp_config_file_buf = BufWriter::new(temp_config_file.as_file());
 
-    let mut cmd = Command::new("nu");
-    let mut args = "".to_string();
-
-    for (name, value) in &info.env_vars.vars {
-        args.push_str(&format!(
-            r#"$env.{} = '{}';"#,
-            name,
-            value.as_os_str().to_str().unwrap()
-        ));
-
-        if String::from("KUBIE_PROMPT_DISABLE").eq(name) && value == "0" {
-            let mut _prompt = info.prompt.clone();
-            // TODO: This is improvable, but it works for now
-            _prompt = _prompt
-                .replace("\\[\\e[31m\\]", "")
-                .replace("\\[\\e[32m\\]", "")
-                .replace("\\[\\e[0m\\]", "")
-                .replace('$', "");
-            let prompt = format!(
-                r#"$env.PROMPT_COMMAND = {{ || $"{prompt}\n(create_left_prompt)" }};"#,
-                prompt = _prompt
-            );
-            args.push_str(prompt.as_str());
-        }
+    // The user's env.nu and config.nu are loaded normally, this file is sourced afterwards.
+    write!(
+        temp_config_file_buf,
+        r#"
+# Set the proper KUBECONFIG variable before each command runs,
+# to prevent the user from overwriting it.
+$env.config = ($env.config | upsert hooks.pre_execution (
+    ($env.config.hooks.pre_execution? | default []) | append {{||
+        $env.KUBECONFIG = $env.KUBIE_KUBECONFIG
+    }}
+))
+
+# Load the variables kubie updates when the context or namespace is switched in place, then set
+# the terminal title and tmux window name, see the title section of the prompt settings.
+$env.config = ($env.config | upsert hooks.pre_prompt (
+    ($env.config.hooks.pre_prompt? | default []) | append {{||
+        if ($env.KUBIE_ENV_FILE | path exists) {{
+            let vars = (open $env.KUBIE_ENV_FILE)
+            let unset = ($vars | columns | where {{|name| ($vars | get $name) == null }})
+            load-env ($vars | reject ...$unset)
+            hide-env -i ...$unset
+        }}
+
+        if ($env.KUBIE_TITLE? | is-not-empty) {{
+            if $env.KUBIE_TITLE_TERMINAL == "1" {{
+                print -n $"\e]2;($env.KUBIE_TITLE)\u{{7}}"
+            }}
+            if $env.KUBIE_TITLE_TMUX == "1" and ($env.TMUX? | is-not-empty) and $env.KUBIE_TITLE != ($env.KUBIE_TMUX_TITLE? | default "") {{
+                $env.KUBIE_TMUX_TITLE = $env.KUBIE_TITLE
+                ^tmux rename-window -t $env.TMUX_PANE $env.KUBIE_TITLE
+            }}
+        }}
+    }}
+))
+"#
+    )?;
+
+    if !info.settings.prompt.disable {
+        // The existing prompt can be a closure or a plain string.
+        write!(
+            temp_config_file_buf,
+            r#"
+def __kubie_render_prompt [original] {{
+    if ($original | describe) == "closure" {{ do $original }} else {{ $original }}
+}}
+
+if $env.KUBIE_NU_USE_RIGHT_PROMPT == "1" {{
+    let original = ($env.PROMPT_COMMAND_RIGHT? | default "")
+    $env.PROMPT_COMMAND_RIGHT = {{|| $"{prompt} (__kubie_render_prompt $original)" }}
+}} else {{
+    let original = ($env.PROMPT_COMMAND? | default "")
+    $env.PROMPT_COMMAND = {{|| $"{prompt} (__kubie_render_prompt $original)" }}
+}}
+"#,
+            prompt = info.prompt,
+        )?;
+    }
+
+    if !info.settings.hooks.start_ctx.is_empty() {
+        write!(temp_config_file_buf, "{}", info.settings.hooks.start_ctx)?;
     }
+
+    temp_config_file_buf.flush()?;
+
+    let mut cmd = Command::new("nu");
     cmd.arg("-e");
-    cmd.arg(args);
+    cmd.arg(format!(
+        "source {}",
+        quote(temp_config_file.path().to_str().expect("Temp file path is not unicode"))
+    ));
+    info.env_vars.apply(&mut cmd);
 
     let mut child = cmd.spawn()?;
     child.wait()?;
+
     Ok(())
 }
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote(r#"/tmp/it's "x"\y"#), r#""/tmp/it's \"x\"\\y""#);
+}
@@ -1,16 +1,19 @@
 use std::env;
-use std::fmt::{self, Display};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
 
-use crate::settings::Settings;
+use crate::kubeconfig::KubeConfig;
+use crate::settings::{ContextMetadata, Settings, Style};
 use crate::shell::ShellKind;
 
 struct Command {
     content: String,
-    shell_kind: ShellKind,
+    shell_kind: Option<ShellKind>,
 }
 
 impl Command {
-    fn new(content: impl Into<String>, shell_kind: ShellKind) -> Command {
+    fn new(content: impl Into<String>, shell_kind: Option<ShellKind>) -> Command {
         Command {
             content: content.into(),
             shell_kind,
@@ -21,104 +24,369 @@ impl Command {
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.shell_kind {
-            ShellKind::Fish => write!(f, "({})", self.content),
+            Some(ShellKind::Fish | ShellKind::Nu) => write!(f, "({})", self.content),
+            // Elvish prompts are functions, the command's output is printed directly.
+            Some(ShellKind::Elvish) => write!(f, "{}", self.content),
             _ => write!(f, "$({})", self.content),
         }
     }
 }
 
-struct Color<D> {
-    color: u32,
-    content: D,
-    shell_kind: ShellKind,
+/// Protect an escape sequence so that the shell will not count it in the length calculation of
+/// the prompt.
+///
+/// The prompt is printed by a command substitution, so bash's `\[..\]` markers are not available and
+/// the equivalent readline markers are used instead.
+fn isolate(shell_kind: Option<ShellKind>, content: &str) -> String {
+    match shell_kind {
+        Some(ShellKind::Bash) => format!("\x01{content}\x02"),
+        Some(ShellKind::Zsh) => format!("%{{{content}%}}"),
+        _ => content.to_string(),
+    }
 }
 
-impl<D> Color<D> {
-    fn new(color: u32, content: D, shell_kind: ShellKind) -> Color<D> {
-        Color {
-            color,
-            content,
-            shell_kind,
-        }
+/// Escape the characters which have a special meaning in the prompt of the given shell.
+fn escape(shell_kind: Option<ShellKind>, content: &str) -> String {
+    match shell_kind {
+        Some(ShellKind::Zsh) => content.replace('%', "%%"),
+        _ => content.to_string(),
     }
 }
 
-// FIXME: @Miuler Validate this implementation for nu shell
-impl<D> Color<D>
-where
-    D: Display,
-{
-    fn isolate<E>(&self, f: &mut fmt::Formatter, content: E) -> fmt::Result
-    where
-        E: Display,
-    {
-        match self.shell_kind {
-            ShellKind::Fish | ShellKind::Xonsh => write!(f, "{content}"),
-            ShellKind::Zsh => write!(f, "%{{{content}%}}"),
-            ShellKind::Bash => write!(f, "\\[{content}\\]"),
-            _ => write!(f, "{content}"),
-        }
+fn colored(shell_kind: Option<ShellKind>, style: &Style, content: &str) -> String {
+    let sgr = style.sgr();
+    if sgr.is_empty() {
+        return escape(shell_kind, content);
     }
+    format!(
+        "{}{}{}",
+        isolate(shell_kind, &format!("\x1b[{sgr}m")),
+        escape(shell_kind, content),
+        isolate(shell_kind, "\x1b[0m"),
+    )
+}
 
-    fn start_color(&self, f: &mut fmt::Formatter, color: u32) -> fmt::Result {
-        match self.shell_kind {
-            ShellKind::Xonsh => self.isolate(f, format!("\\033[{color}m")),
-            _ => self.isolate(f, format!("\\e[{color}m")),
-        }
-    }
+/// Piece of a prompt template.
+#[derive(Debug, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    /// A `{name}` placeholder replaced by a value such as the context name.
+    Placeholder(String),
+    /// A `{?...}` section which is only rendered if all of its placeholders have a value.
+    Optional(Vec<Piece>),
+}
 
-    fn end_color(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.shell_kind {
-            ShellKind::Xonsh => self.isolate(f, "\\033[0m"),
-            _ => self.isolate(f, "\\e[0m"),
+/// Parse a prompt template. Use `{{` and `}}` for literal braces.
+fn parse_template(chars: &mut Peekable<Chars>, nested: bool) -> Vec<Piece> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' if nested => break,
+            '{' if chars.peek() == Some(&'?') => {
+                chars.next();
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+                pieces.push(Piece::Optional(parse_template(chars, true)));
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if closed {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    pieces.push(Piece::Placeholder(name));
+                } else {
+                    text.push('{');
+                    text.push_str(&name);
+                }
+            }
+            c => text.push(c),
         }
     }
+
+    pieces.push(Piece::Text(text));
+    pieces.retain(|p| !matches!(p, Piece::Text(t) if t.is_empty()));
+    pieces
 }
 
-impl<D> fmt::Display for Color<D>
-where
-    D: Display,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.start_color(f, self.color)?;
-        write!(f, "{}", self.content)?;
-        self.end_color(f)?;
-        Ok(())
-    }
+/// Values available to the placeholders of the prompt template.
+struct PromptValues<'a> {
+    settings: &'a Settings,
+    shell_kind: Option<ShellKind>,
+    colors: bool,
+    context_name: &'a str,
+    namespace_name: &'a str,
+    cluster_name: &'a str,
+    user_name: &'a str,
+    depth: u32,
+    metadata: ContextMetadata,
 }
 
-const RED: u32 = 31;
-const GREEN: u32 = 32;
-const BLUE: u32 = 34;
+impl PromptValues<'_> {
+    fn get(&self, placeholder: &str) -> Option<String> {
+        let value = match placeholder {
+            "ctx" => self.context_name.to_string(),
+            "ns" => self.namespace_name.to_string(),
+            "cluster" => self.cluster_name.to_string(),
+            "user" => self.user_name.to_string(),
+            "alias" => self.metadata.alias.as_deref().unwrap_or(self.context_name).to_string(),
+            "tags" => self.metadata.tags_string(),
+            "depth" if self.settings.prompt.show_depth && self.depth > 1 => self.depth.to_string(),
+            "depth" => String::new(),
+            p => match p.strip_prefix("tag:") {
+                Some(key) => self.metadata.tags.get(key).cloned().unwrap_or_default(),
+                None => return None,
+            },
+        };
+        Some(value).filter(|v| !v.is_empty())
+    }
+
+    /// Render the pieces, returning `None` if one of the placeholders has no value.
+    fn render(&self, pieces: &[Piece]) -> Option<String> {
+        let mut output = String::new();
+        let mut complete = true;
+        for piece in pieces {
+            match piece {
+                Piece::Text(text) => output.push_str(&escape(self.shell_kind, text)),
+                Piece::Placeholder(name) => match self.get(name) {
+                    Some(value) if self.colors => {
+                        let style = self.settings.prompt.style(self.context_name, name);
+                        output.push_str(&colored(self.shell_kind, &style, &value));
+                    }
+                    Some(value) => output.push_str(&escape(self.shell_kind, &value)),
+                    None => complete = false,
+                },
+                Piece::Optional(pieces) => output.push_str(&self.render(pieces).unwrap_or_default()),
+            }
+        }
+        complete.then_some(output)
+    }
+}
 
 /// Generates a PS1 string that shows the current context, namespace and depth.
 ///
-/// Makes sure to protect the escape sequences so that the shell will not count the escape
-/// sequences in the length calculation of the prompt.
-pub fn generate_ps1(settings: &Settings, depth: u32, shell_kind: ShellKind) -> String {
+/// The PS1 calls `kubie info prompt` once per prompt, which renders the whole segment with
+/// `render_prompt`. This avoids spawning one kubie process per piece of information.
+///
+/// Shells kubie does not know get a POSIX command substitution.
+pub fn generate_ps1(shell_kind: Option<ShellKind>) -> String {
     let current_exe_path = env::current_exe().expect("Could not get own binary path");
     let current_exe_path_str = current_exe_path.to_str().expect("Binary path is not unicode");
 
-    let mut parts = vec![];
-    parts.push(
-        Color::new(
-            RED,
-            Command::new(format!("{current_exe_path_str} info ctx"), shell_kind),
-            shell_kind,
-        )
-        .to_string(),
+    let current_exe_path_str = match shell_kind {
+        Some(ShellKind::Nu) => format!("^{}", super::nu::quote(current_exe_path_str)),
+        Some(ShellKind::Pwsh) => format!("& {}", super::pwsh::quote(current_exe_path_str)),
+        Some(ShellKind::Elvish) => super::elvish::quote(current_exe_path_str),
+        _ => current_exe_path_str.to_string(),
+    };
+
+    Command::new(format!("{current_exe_path_str} info prompt"), shell_kind).to_string()
+}
+
+/// Render the prompt segment for the given kubeconfig using the template from the settings, escaped
+/// for the given shell. Prompt frameworks apply their own styles, so they render without colors.
+pub fn render_prompt(
+    settings: &Settings,
+    shell_kind: Option<ShellKind>,
+    colors: bool,
+    config: &KubeConfig,
+    depth: u32,
+) -> String {
+    render_template(&settings.prompt.format(), settings, shell_kind, colors, config, depth)
+}
+
+/// Render the terminal title for the given kubeconfig using the title template from the settings.
+pub fn render_title(settings: &Settings, config: &KubeConfig, depth: u32) -> String {
+    render_template(&settings.prompt.title.format, settings, None, false, config, depth)
+}
+
+fn render_template(
+    template: &str,
+    settings: &Settings,
+    shell_kind: Option<ShellKind>,
+    colors: bool,
+    config: &KubeConfig,
+    depth: u32,
+) -> String {
+    let context_name = config.current_context.as_deref().unwrap_or("");
+    let context = config.contexts.first().map(|c| &c.context);
+
+    let values = PromptValues {
+        settings,
+        shell_kind,
+        colors,
+        context_name,
+        namespace_name: context.and_then(|c| c.namespace.as_deref()).unwrap_or("default"),
+        cluster_name: context.map(|c| c.cluster.as_str()).unwrap_or(""),
+        user_name: context.map(|c| c.user.as_str()).unwrap_or(""),
+        depth,
+        metadata: settings.context_metadata(context_name),
+    };
+
+    let pieces = parse_template(&mut template.chars().peekable(), false);
+    // Missing values at the top level are rendered as empty, only optional sections disappear.
+    let mut output = String::new();
+    for piece in &pieces {
+        output.push_str(&values.render(std::slice::from_ref(piece)).unwrap_or_default());
+    }
+    output
+}
+
+#[cfg(test)]
+fn test_config() -> KubeConfig {
+    serde_yaml::from_str(
+        r#"
+clusters: []
+users: []
+contexts:
+  - name: prod-eu
+    context: {cluster: eks, user: admin, namespace: kube-system}
+current-context: prod-eu
+"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_render_prompt_bash() {
+    let settings = Settings::default();
+    let prompt = render_prompt(&settings, Some(ShellKind::Bash), true, &test_config(), 2);
+    assert_eq!(
+        prompt,
+        "[\x01\x1b[31m\x02prod-eu\x01\x1b[0m\x02|\x01\x1b[32m\x02kube-system\x01\x1b[0m\x02|\x01\x1b[34m\x022\x01\x1b[0m\x02]"
     );
-    parts.push(
-        Color::new(
-            GREEN,
-            Command::new(format!("{current_exe_path_str} info ns"), shell_kind),
-            shell_kind,
-        )
-        .to_string(),
+}
+
+#[test]
+fn test_render_prompt_zsh_escapes_percent() {
+    let settings = Settings::default();
+    let mut config = test_config();
+    config.current_context = Some("100%".into());
+    let prompt = render_prompt(&settings, Some(ShellKind::Zsh), true, &config, 1);
+    assert_eq!(
+        prompt,
+        "[%{\x1b[31m%}100%%%{\x1b[0m%}|%{\x1b[32m%}kube-system%{\x1b[0m%}]"
+    );
+}
+
+#[test]
+fn test_render_prompt_without_colors() {
+    let settings = Settings::default();
+    let prompt = render_prompt(&settings, Some(ShellKind::Zsh), false, &test_config(), 1);
+    assert_eq!(prompt, "[prod-eu|kube-system]");
+}
+
+#[test]
+fn test_parse_template() {
+    let pieces = parse_template(&mut "[{ctx}{?|{depth}}] {{x}}".chars().peekable(), false);
+    assert_eq!(
+        pieces,
+        vec![
+            Piece::Text("[".into()),
+            Piece::Placeholder("ctx".into()),
+            Piece::Optional(vec![Piece::Text("|".into()), Piece::Placeholder("depth".into())]),
+            Piece::Text("] {x}".into()),
+        ]
     );
-    if settings.prompt.show_depth && depth > 1 {
-        parts.push(Color::new(BLUE, depth, shell_kind).to_string());
+}
+
+#[test]
+fn test_render_prompt_template() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+prompt:
+  format: "{alias}@{cluster}{? env={tag:env}}{? team={tag:team}} {user}"
+  colors:
+    alias: none
+    cluster: none
+    tags: none
+contexts:
+  - pattern: prod-eu
+    alias: pe
+    tags:
+      env: prod
+"#,
+    )
+    .unwrap();
+    let prompt = render_prompt(&settings, None, true, &test_config(), 1);
+    assert_eq!(prompt, "pe@eks env=prod admin");
+}
+
+/// Compare `kubie info prompt` with the two `kubie info ctx` and `kubie info ns` calls it replaces,
+/// on a large kubeconfig. Timings depend on the machine, they are printed and not asserted.
+/// Run with `cargo test bench_render_prompt -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_render_prompt() {
+    use std::fmt::Write;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    use crate::ioutil;
+
+    let certificate = "A".repeat(2048);
+    let mut yaml = String::from("current-context: ctx-0\nclusters:\n");
+    for i in 0..500 {
+        writeln!(
+            yaml,
+            "  - name: cluster-{i}\n    cluster: {{server: 'https://{i}.example.com', certificate-authority-data: {certificate}}}"
+        )
+        .unwrap();
+    }
+    yaml.push_str("users:\n");
+    for i in 0..500 {
+        writeln!(yaml, "  - name: user-{i}\n    user: {{token: {certificate}}}").unwrap();
+    }
+    yaml.push_str("contexts:\n");
+    for i in 0..500 {
+        writeln!(
+            yaml,
+            "  - name: ctx-{i}\n    context: {{cluster: cluster-{i}, user: user-{i}, namespace: ns-{i}}}"
+        )
+        .unwrap();
+    }
+    let temp_config_file = tempfile::Builder::new()
+        .prefix("kubie-config")
+        .suffix(".yaml")
+        .tempfile()
+        .unwrap();
+    std::fs::write(temp_config_file.path(), yaml).unwrap();
+
+    let settings = Settings::default();
+    let iterations = 20;
+
+    // `kubie info prompt`: the kubeconfig is read once and the whole prompt rendered.
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let config: KubeConfig = ioutil::read_yaml(temp_config_file.path()).unwrap();
+        black_box(render_prompt(&settings, Some(ShellKind::Bash), true, &config, 1));
+    }
+    let prompt = start.elapsed() / iterations;
+
+    // `kubie info ctx` and `kubie info ns`: each call reads the kubeconfig.
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let config: KubeConfig = ioutil::read_yaml(temp_config_file.path()).unwrap();
+        black_box(config.current_context);
+        let config: KubeConfig = ioutil::read_yaml(temp_config_file.path()).unwrap();
+        black_box(config.contexts[0].context.namespace.clone());
     }
+    let info_ctx_ns = start.elapsed() / iterations;
 
-    format!("[{}]", parts.join("|"))
+    println!("kubie info prompt: {prompt:?}, kubie info ctx + kubie info ns: {info_ctx_ns:?}");
 }
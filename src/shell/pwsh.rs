@@ -0,0 +1,99 @@
+use std::io::{BufWriter, Write};
+use std::process::Command;
+
+use anyhow::Result;
+
+use super::ShellSpawnInfo;
+
+/// Quote a value as a PowerShell verbatim string.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
+    let temp_rc_file = tempfile::Builder::new()
+        .prefix("kubie-pwshrc")
+        .suffix(".ps1")
+        .tempfile()?;
+    let mut temp_rc_file_buf = BufWriter::new(temp_rc_file.as_file());
+
+    // The user's profile is loaded by pwsh before this file is dot-sourced.
+    write!(
+        temp_rc_file_buf,
+        r#"
+# PowerShell has no hook running before each command, set the proper KUBECONFIG variable when
+# PSReadLine accepts a line, to prevent the user from overwriting it.
+if (Get-Module PSReadLine) {{
+    Set-PSReadLineKeyHandler -Key Enter -ScriptBlock {{
+        $env:KUBECONFIG = $env:KUBIE_KUBECONFIG
+        [Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine()
+    }}
+}}
+
+function __kubie_before_prompt__ {{
+    $env:KUBECONFIG = $env:KUBIE_KUBECONFIG
+
+    # Load the variables kubie updates when the context or namespace is switched in place.
+    if (Test-Path -PathType Leaf $env:KUBIE_ENV_FILE) {{
+        . $env:KUBIE_ENV_FILE
+    }}
+
+    # Set the terminal title and tmux window name, see the title section of the prompt settings.
+    if ($env:KUBIE_TITLE) {{
+        if ($env:KUBIE_TITLE_TERMINAL -eq "1") {{
+            $Host.UI.RawUI.WindowTitle = $env:KUBIE_TITLE
+        }}
+        if ($env:KUBIE_TITLE_TMUX -eq "1" -and $env:TMUX -and $env:KUBIE_TITLE -ne $global:__kubie_tmux_title__) {{
+            $global:__kubie_tmux_title__ = $env:KUBIE_TITLE
+            tmux rename-window -t $env:TMUX_PANE $env:KUBIE_TITLE
+        }}
+    }}
+}}
+
+$function:__kubie_original_prompt__ = $function:prompt
+"#
+    )?;
+
+    if info.settings.prompt.disable {
+        write!(
+            temp_rc_file_buf,
+            r#"
+function prompt {{
+    __kubie_before_prompt__
+    __kubie_original_prompt__
+}}
+"#
+        )?;
+    } else {
+        write!(
+            temp_rc_file_buf,
+            r#"
+function prompt {{
+    __kubie_before_prompt__
+    "{} $(__kubie_original_prompt__)"
+}}
+"#,
+            info.prompt,
+        )?;
+    }
+
+    if !info.settings.hooks.start_ctx.is_empty() {
+        write!(temp_rc_file_buf, "{}", info.settings.hooks.start_ctx)?;
+    }
+
+    temp_rc_file_buf.flush()?;
+
+    let mut cmd = Command::new("pwsh");
+    cmd.arg("-NoExit");
+    cmd.arg("-Command");
+    cmd.arg(format!(
+        ". {}",
+        quote(temp_rc_file.path().to_str().expect("Temp file path is not unicode"))
+    ));
+    info.env_vars.apply(&mut cmd);
+
+    let mut child = cmd.spawn()?;
+    child.wait()?;
+
+    Ok(())
+}
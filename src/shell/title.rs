@@ -0,0 +1,85 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::Command;
+
+use crate::settings::Title;
+
+/// Push the current title on the terminal's title stack. Supported by xterm compatible terminals.
+const PUSH_TITLE: &str = "\x1b[22;0t";
+/// Pop the title pushed by `PUSH_TITLE`, restoring it.
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// State of the tmux window before the kubie shell renamed it.
+struct TmuxWindow {
+    pane: String,
+    name: String,
+    automatic_rename: bool,
+}
+
+impl TmuxWindow {
+    fn current() -> Option<TmuxWindow> {
+        env::var_os("TMUX")?;
+        let pane = env::var("TMUX_PANE").ok()?;
+        let output = Command::new("tmux")
+            .args(["display-message", "-p", "-t", &pane, "#{automatic-rename}\t#W"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let stdout = String::from_utf8(output.stdout).ok()?;
+        let (automatic_rename, name) = stdout.trim_end_matches('\n').split_once('\t')?;
+        Some(TmuxWindow {
+            pane,
+            name: name.into(),
+            automatic_rename: automatic_rename == "1",
+        })
+    }
+
+    fn restore(&self) {
+        // Renaming the window disables automatic renaming, so it must be restored after the name.
+        let _ = Command::new("tmux")
+            .args(["rename-window", "-t", &self.pane, &self.name])
+            .status();
+        if self.automatic_rename {
+            let _ = Command::new("tmux")
+                .args(["set-window-option", "-t", &self.pane, "automatic-rename", "on"])
+                .status();
+        }
+    }
+}
+
+/// Saves the terminal title and tmux window name before a kubie shell is spawned, and restores them
+/// when dropped. The shell hooks take care of setting the title while the shell runs.
+pub struct TitleGuard {
+    terminal: bool,
+    tmux_window: Option<TmuxWindow>,
+}
+
+impl TitleGuard {
+    pub fn new(title: &Title) -> TitleGuard {
+        let terminal = title.terminal && io::stdout().is_terminal();
+        if terminal {
+            print!("{PUSH_TITLE}");
+            let _ = io::stdout().flush();
+        }
+
+        TitleGuard {
+            terminal,
+            tmux_window: if title.tmux { TmuxWindow::current() } else { None },
+        }
+    }
+}
+
+impl Drop for TitleGuard {
+    fn drop(&mut self) {
+        if self.terminal {
+            print!("{POP_TITLE}");
+            let _ = io::stdout().flush();
+        }
+        if let Some(tmux_window) = &self.tmux_window {
+            tmux_window.restore();
+        }
+    }
+}
@@ -33,6 +33,25 @@ if Path("~/.config/xonsh/rc.d").is_dir():
 @events.on_precommand
 def __kubie_cmd_pre_exec__(cmd):
     $KUBECONFIG = $KUBIE_KUBECONFIG
+
+# Load the variables kubie updates when the context or namespace is switched in place.
+@events.on_pre_prompt
+def __kubie_sync_env__():
+    import json
+    if Path($KUBIE_ENV_FILE).is_file():
+        for name, value in json.loads(Path($KUBIE_ENV_FILE).read_text()).items():
+            if value is None:
+                ${{...}}.pop(name, None)
+            else:
+                ${{...}}[name] = value
+
+    # Set the terminal title and tmux window name, see the title section of the prompt settings.
+    if "KUBIE_TITLE" in ${{...}}:
+        if $KUBIE_TITLE_TERMINAL == "1":
+            $TITLE = $KUBIE_TITLE.replace('{{', '{{{{').replace('}}', '}}}}')
+        if $KUBIE_TITLE_TMUX == "1" and "TMUX" in ${{...}} and $KUBIE_TITLE != ${{...}}.get("KUBIE_TMUX_TITLE"):
+            $KUBIE_TMUX_TITLE = $KUBIE_TITLE
+            tmux rename-window -t $TMUX_PANE $KUBIE_TITLE
 "#
     )?;
 
@@ -61,6 +80,10 @@ del $KUBIE_PROMPT
         )?;
     }
 
+    if !info.settings.hooks.start_ctx.is_empty() {
+        write!(temp_rc_file_buf, "{}", info.settings.hooks.start_ctx)?;
+    }
+
     temp_rc_file_buf.flush()?;
 
     let mut cmd = Command::new("xonsh");
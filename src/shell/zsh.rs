@@ -89,6 +89,33 @@ function __kubie_cmd_pre_exec__() {{
 }}
 
 add-zsh-hook preexec __kubie_cmd_pre_exec__
+
+# Load the variables kubie updates when the context or namespace is switched in place.
+function __kubie_sync_env__() {{
+    if [[ -f "$KUBIE_ENV_FILE" ]] ; then
+        source "$KUBIE_ENV_FILE"
+    fi
+}}
+
+# Run before the other precmd hooks so that prompt themes see the up to date values.
+precmd_functions=(__kubie_sync_env__ $precmd_functions)
+
+# Set the terminal title and tmux window name, see the title section of the prompt settings.
+# Run after the other precmd hooks so that themes setting the title do not overwrite it.
+function __kubie_set_title__() {{
+    if [[ -z "$KUBIE_TITLE" ]] ; then
+        return
+    fi
+    if [[ "$KUBIE_TITLE_TERMINAL" == "1" ]] ; then
+        printf '\033]2;%s\007' "$KUBIE_TITLE"
+    fi
+    if [[ "$KUBIE_TITLE_TMUX" == "1" && -n "$TMUX" && "$KUBIE_TITLE" != "$__kubie_tmux_title__" ]] ; then
+        __kubie_tmux_title__="$KUBIE_TITLE"
+        tmux rename-window -t "$TMUX_PANE" "$KUBIE_TITLE"
+    fi
+}}
+
+add-zsh-hook precmd __kubie_set_title__
 "#,
         )?;
 
@@ -145,23 +172,5 @@ add-zsh-hook precmd __kubie_cmd_pre_cmd__
     let mut child = cmd.spawn()?;
     child.wait()?;
 
-    if !info.settings.hooks.stop_ctx.is_empty() {
-        let temp_exit_hook_file = tempfile::Builder::new()
-            .prefix("kubie-zsh-exit-hook")
-            .suffix(".zsh")
-            .tempfile()?;
-        let mut temp_exit_hook_file_buf = BufWriter::new(temp_exit_hook_file.as_file());
-
-        write!(temp_exit_hook_file_buf, "{}", info.settings.hooks.stop_ctx)?;
-
-        temp_exit_hook_file_buf.flush()?;
-        let mut exit_cmd = Command::new("zsh");
-        exit_cmd.arg(temp_exit_hook_file.path());
-        info.env_vars.apply(&mut exit_cmd);
-
-        let mut child = exit_cmd.spawn()?;
-        child.wait()?;
-    }
-
     Ok(())
 }
@@ -1,5 +1,7 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::DirBuilder;
-use std::{collections::HashMap, panic::UnwindSafe};
+use std::panic::UnwindSafe;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
@@ -18,6 +20,9 @@ pub mod paths {
         };
         static ref KUBIE_STATE_PATH: PathBuf = KUBIE_DATA_DIR.join("state.json");
         static ref KUBIE_STATE_LOCK_PATH: PathBuf = KUBIE_DATA_DIR.join(".state.json.lock");
+        static ref KUBIE_EXPORTS_DIR: PathBuf = KUBIE_DATA_DIR.join("exports");
+        static ref KUBIE_GLOBAL_KUBECONFIG_PATH: PathBuf = KUBIE_DATA_DIR.join("kubeconfig.yaml");
+        static ref KUBIE_GLOBAL_SESSION_PATH: PathBuf = KUBIE_DATA_DIR.join("session.json");
     }
 
     #[inline]
@@ -34,6 +39,23 @@ pub mod paths {
     pub fn state_lock() -> &'static Path {
         &KUBIE_STATE_LOCK_PATH
     }
+
+    #[inline]
+    pub fn exports_dir() -> &'static Path {
+        &KUBIE_EXPORTS_DIR
+    }
+
+    /// Kubeconfig switched by `kubie ctx` and `kubie ns` in global mode.
+    #[inline]
+    pub fn global_kubeconfig() -> &'static Path {
+        &KUBIE_GLOBAL_KUBECONFIG_PATH
+    }
+
+    /// Session of the global kubeconfig, used to switch back to the previous context or namespace.
+    #[inline]
+    pub fn global_session() -> &'static Path {
+        &KUBIE_GLOBAL_SESSION_PATH
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
@@ -43,9 +65,132 @@ pub struct State {
     ///
     /// The key represents the name of the context and the value is the namespace's name.
     pub namespace_history: HashMap<String, Option<String>>,
+
+    /// Namespaces of each context, as of the last time they were listed with kubectl. Used to
+    /// complete namespace names without calling the API server.
+    #[serde(default)]
+    pub namespace_cache: HashMap<String, Vec<String>>,
+
+    /// Files written in the exports directory by `kubie export` and `kubie env`, keyed by path.
+    #[serde(default)]
+    pub exports: BTreeMap<String, ExportEntry>,
+
+    /// Contexts and namespaces entered, the most recently used first.
+    #[serde(default)]
+    pub history: Vec<HistoryRecord>,
+
+    /// Contexts pinned with `kubie pin`, listed first in the picker.
+    #[serde(default)]
+    pub pinned_contexts: BTreeSet<String>,
+
+    /// Namespaces pinned with `kubie pin <context>/<namespace>`, keyed by context.
+    #[serde(default)]
+    pub pinned_namespaces: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Context and namespace entered with `kubie ctx`, `kubie ns` or `kubie env`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryRecord {
+    pub context: String,
+    pub namespace: Option<String>,
+    /// Last time the pair was entered, in seconds since the Unix epoch.
+    pub last_used: u64,
+    /// Number of times the pair was entered.
+    pub count: u32,
+    /// Uses of the pair, each decayed by its age at `last_used`, see `frecency`.
+    #[serde(default)]
+    pub score: f64,
+}
+
+/// Kubeconfig file written in the exports directory.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExportEntry {
+    pub context: String,
+    pub namespace: String,
+    /// Time after which the file can be removed, in seconds since the Unix epoch.
+    pub expires: u64,
+    /// Process which requested the file. The file is in use as long as this process is running.
+    pub owner: Option<u32>,
+}
+
+/// Age after which a use of a pair counts for half as much in its frecency.
+const FRECENCY_HALF_LIFE: f64 = 7.0 * 86400.0;
+
+impl HistoryRecord {
+    /// Score of the pair combining how often and how recently it was used. Every use counts for 1
+    /// when it happens, and its weight halves every `FRECENCY_HALF_LIFE`.
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_used) as f64;
+        self.score * 0.5f64.powf(age / FRECENCY_HALF_LIFE)
+    }
+}
+
+/// Number of entries kept in the history.
+const HISTORY_SIZE: usize = 100;
+
+/// Current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
 }
 
 impl State {
+    /// Record that the context and namespace were entered, moving the pair to the top of the
+    /// history.
+    pub fn record_history(&mut self, context: &str, namespace: Option<&str>) {
+        self.record_history_at(context, namespace, now());
+    }
+
+    fn record_history_at(&mut self, context: &str, namespace: Option<&str>, now: u64) {
+        let mut record = match self
+            .history
+            .iter()
+            .position(|r| r.context == context && r.namespace.as_deref() == namespace)
+        {
+            Some(idx) => self.history.remove(idx),
+            None => HistoryRecord {
+                context: context.into(),
+                namespace: namespace.map(Into::into),
+                last_used: 0,
+                count: 0,
+                score: 0.0,
+            },
+        };
+        record.score = record.frecency(now) + 1.0;
+        record.last_used = now;
+        record.count += 1;
+        self.history.insert(0, record);
+        self.history.truncate(HISTORY_SIZE);
+    }
+
+    pub fn is_namespace_pinned(&self, context: &str, namespace: &str) -> bool {
+        self.pinned_namespaces
+            .get(context)
+            .is_some_and(|namespaces| namespaces.contains(namespace))
+    }
+
+    /// Frecency of the context, summed over all the namespaces used in the context.
+    pub fn context_frecency(&self, context: &str) -> f64 {
+        let now = now();
+        self.history
+            .iter()
+            .filter(|r| r.context == context)
+            .map(|r| r.frecency(now))
+            .sum()
+    }
+
+    /// Frecency of the namespace in the context.
+    pub fn namespace_frecency(&self, context: &str, namespace: &str) -> f64 {
+        let now = now();
+        self.history
+            .iter()
+            .filter(|r| r.context == context && r.namespace.as_deref() == Some(namespace))
+            .map(|r| r.frecency(now))
+            .sum()
+    }
+
     /// Loads the state.json from the filesystem, waiting for a file lock to ensure no other
     /// concurrent Kubie processes are accessing/writing the file at the same time.
     pub fn load() -> Result<State> {
@@ -89,3 +234,43 @@ impl State {
             .with_context(|| format!("Failed to write state to '{}'", paths::state().display()))
     }
 }
+
+#[test]
+fn test_record_history() {
+    let mut state = State::default();
+    state.record_history("prod", Some("kube-system"));
+    state.record_history("dev", None);
+    state.record_history("prod", Some("kube-system"));
+
+    let pairs: Vec<_> = state
+        .history
+        .iter()
+        .map(|r| (r.context.as_str(), r.namespace.as_deref(), r.count))
+        .collect();
+    assert_eq!(pairs, vec![("prod", Some("kube-system"), 2), ("dev", None, 1)]);
+}
+
+#[test]
+fn test_frecency() {
+    let year_ago = now() - 365 * 86400;
+    let mut state = State::default();
+    // Used a lot a long time ago, and once today.
+    for _ in 0..1000 {
+        state.record_history_at("old", Some("default"), year_ago);
+    }
+    state.record_history("old", Some("default"));
+    state.record_history("dev", Some("default"));
+    state.record_history("dev", Some("kube-system"));
+    state.record_history("dev", Some("kube-system"));
+
+    let old = state.context_frecency("old");
+    assert!((1.0..1.01).contains(&old), "{old}");
+    assert!(state.context_frecency("dev") > 2.99);
+    assert!(state.namespace_frecency("dev", "kube-system") > state.namespace_frecency("dev", "default"));
+    assert_eq!(state.context_frecency("staging"), 0.0);
+
+    // Without new uses, the score halves every half-life.
+    let record = &state.history[0];
+    let later = record.last_used + FRECENCY_HALF_LIFE as u64;
+    assert!((record.frecency(later) - record.frecency(record.last_used) / 2.0).abs() < 1e-9);
+}